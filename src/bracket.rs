@@ -1,22 +1,22 @@
+use crate::pairs::PairTable;
+use crate::report::{self, Report};
+use anyhow::{bail, Result};
 use either::Either;
 use std::fmt::{Display, Formatter};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BracketShape {
     Round,
     Angular,
     Square,
     Curly,
+    // The nth user-defined pair of a `PairTable`
+    Custom(usize),
 }
 
 impl BracketShape {
     pub fn close(&self) -> Bracket {
-        match *self {
-            BracketShape::Round => Bracket::Close(*self),
-            BracketShape::Angular => Bracket::Close(*self),
-            BracketShape::Square => Bracket::Close(*self),
-            BracketShape::Curly => Bracket::Close(*self),
-        }
+        Bracket::Close(*self)
     }
 }
 
@@ -35,9 +35,11 @@ impl Bracket {
     }
 }
 
-impl From<char> for Bracket {
-    fn from(input: char) -> Self {
-        match input {
+impl TryFrom<char> for Bracket {
+    type Error = anyhow::Error;
+
+    fn try_from(input: char) -> Result<Self> {
+        Ok(match input {
             '(' => Bracket::Open(BracketShape::Round),
             ')' => Bracket::Close(BracketShape::Round),
             '{' => Bracket::Open(BracketShape::Curly),
@@ -46,32 +48,35 @@ impl From<char> for Bracket {
             ']' => Bracket::Close(BracketShape::Square),
             '<' => Bracket::Open(BracketShape::Angular),
             '>' => Bracket::Close(BracketShape::Angular),
-            _ => panic!("Invalid char"),
-        }
+            _ => bail!("Not a bracket: {:?}", input),
+        })
     }
 }
 
 impl Display for Bracket {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match *self {
-                Bracket::Open(BracketShape::Round) => '(',
-                Bracket::Close(BracketShape::Round) => ')',
-                Bracket::Open(BracketShape::Curly) => '{',
-                Bracket::Close(BracketShape::Curly) => '}',
-                Bracket::Open(BracketShape::Square) => '[',
-                Bracket::Close(BracketShape::Square) => ']',
-                Bracket::Open(BracketShape::Angular) => '<',
-                Bracket::Close(BracketShape::Angular) => '>',
+        match *self {
+            Bracket::Open(BracketShape::Round) => write!(f, "("),
+            Bracket::Close(BracketShape::Round) => write!(f, ")"),
+            Bracket::Open(BracketShape::Curly) => write!(f, "{{"),
+            Bracket::Close(BracketShape::Curly) => write!(f, "}}"),
+            Bracket::Open(BracketShape::Square) => write!(f, "["),
+            Bracket::Close(BracketShape::Square) => write!(f, "]"),
+            Bracket::Open(BracketShape::Angular) => write!(f, "<"),
+            Bracket::Close(BracketShape::Angular) => write!(f, ">"),
+            // Only the `PairTable` that defined a custom shape can spell it
+            Bracket::Open(BracketShape::Custom(_)) | Bracket::Close(BracketShape::Custom(_)) => {
+                write!(f, "{:?}", self)
             }
-        )
+        }
     }
 }
 
+// Anything that isn't one of the four bracket pairs is skipped
 pub fn parse_line(line: &str) -> Vec<Bracket> {
-    line.chars().map(Bracket::from).collect()
+    line.chars()
+        .filter_map(|c| Bracket::try_from(c).ok())
+        .collect()
 }
 
 pub fn parse_lines(input: &str) -> Vec<Vec<Bracket>> {
@@ -96,79 +101,23 @@ pub fn lint_line(line: &[Bracket]) -> Either<Vec<BracketShape>, LintError> {
                 stack.push(bracket.shape());
                 open_pos.push(0)
             }
-            Some(&current) => match current {
-                BracketShape::Round => match bracket {
-                    Bracket::Open(shape) => {
-                        stack.push(*shape);
-                        open_pos.push(i);
-                    }
-                    Bracket::Close(BracketShape::Round) => {
-                        stack.pop();
-                        open_pos.pop();
-                    }
-                    _ => {
-                        return Either::Right(LintError {
-                            expected: BracketShape::Round,
-                            found: bracket.shape(),
-                            open_pos: *open_pos.last().unwrap(),
-                            err_pos: i,
-                        });
-                    }
-                },
-                BracketShape::Angular => match bracket {
-                    Bracket::Open(shape) => {
-                        stack.push(*shape);
-                        open_pos.push(i);
-                    }
-                    Bracket::Close(BracketShape::Angular) => {
-                        stack.pop();
-                        open_pos.pop();
-                    }
-                    _ => {
-                        return Either::Right(LintError {
-                            expected: BracketShape::Angular,
-                            found: bracket.shape(),
-                            open_pos: *open_pos.last().unwrap(),
-                            err_pos: i,
-                        });
-                    }
-                },
-                BracketShape::Square => match bracket {
-                    Bracket::Open(shape) => {
-                        stack.push(*shape);
-                        open_pos.push(i);
-                    }
-                    Bracket::Close(BracketShape::Square) => {
-                        stack.pop();
-                        open_pos.pop();
-                    }
-                    _ => {
-                        return Either::Right(LintError {
-                            expected: BracketShape::Square,
-                            found: bracket.shape(),
-                            open_pos: *open_pos.last().unwrap(),
-                            err_pos: i,
-                        });
-                    }
-                },
-                BracketShape::Curly => match bracket {
-                    Bracket::Open(shape) => {
-                        stack.push(*shape);
-                        open_pos.push(i);
-                    }
-                    Bracket::Close(BracketShape::Curly) => {
-                        stack.pop();
-                        open_pos.pop();
-                    }
-                    _ => {
-                        return Either::Right(LintError {
-                            expected: BracketShape::Curly,
-                            found: bracket.shape(),
-                            open_pos: *open_pos.last().unwrap(),
-                            err_pos: i,
-                        });
-                    }
-                },
+            Some(&current) => match bracket {
+                Bracket::Open(shape) => {
+                    stack.push(*shape);
+                    open_pos.push(i);
+                }
+                Bracket::Close(shape) if *shape == current => {
+                    stack.pop();
+                    open_pos.pop();
+                }
+                _ => {
+                    return Either::Right(LintError {
+                        expected: current,
+                        found: bracket.shape(),
+                        open_pos: *open_pos.last().unwrap(),
+                        err_pos: i,
+                    });
+                }
             },
         }
     }
@@ -199,6 +148,7 @@ pub fn to_complete_score(shape: &BracketShape) -> usize {
         BracketShape::Angular => 4,
         BracketShape::Square => 2,
         BracketShape::Curly => 3,
+        BracketShape::Custom(_) => 0,
     }
 }
 
@@ -241,6 +191,7 @@ pub fn to_error_score(shape: &BracketShape) -> usize {
         BracketShape::Angular => 25137,
         BracketShape::Square => 57,
         BracketShape::Curly => 1197,
+        BracketShape::Custom(_) => 0,
     }
 }
 
@@ -258,39 +209,233 @@ pub fn calc_syntax_score(lines: &[Vec<Bracket>]) -> usize {
     score
 }
 
-pub fn lint_graphically(lines: &[Vec<Bracket>]) {
-    let mut score = 0;
+// Underlines every finding of every line, see `report::write_terminal`
+pub fn lint_graphically(
+    out: &mut impl std::io::Write,
+    lines: &[Vec<Bracket>],
+    table: &PairTable,
+) -> std::io::Result<()> {
+    report::write_terminal(out, &Report::lint_brackets(lines, table))
+}
 
-    for line in lines {
-        let lint = lint_line(line);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fix {
+    Insert { pos: usize, brackets: Vec<Bracket> },
+    Delete { pos: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    Mismatched {
+        expected: BracketShape,
+        found: BracketShape,
+        open_pos: usize,
+    },
+    Unopened {
+        found: BracketShape,
+    },
+    Unclosed {
+        expected: BracketShape,
+        open_pos: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub problem: Problem,
+    pub pos: usize,
+    pub severity: Severity,
+    pub fix: Fix,
+}
 
-        for c in line {
-            print!("{}", c);
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
         }
-        println!();
+    }
+}
 
-        if let Either::Right(lint_err) = lint {
-            let line_score = to_error_score(&lint_err.found);
+impl Diagnostic {
+    // Brackets are written the way `table` spells them
+    pub fn message(&self, table: &PairTable) -> String {
+        match &self.problem {
+            Problem::Mismatched {
+                expected, found, ..
+            } => format!(
+                "expected {} to close {}, found {}",
+                table.render(&expected.close()),
+                table.render(&Bracket::Open(*expected)),
+                table.render(&found.close())
+            ),
+            Problem::Unopened { found } => {
+                format!("{} closes nothing", table.render(&found.close()))
+            }
+            Problem::Unclosed { expected, .. } => {
+                format!(
+                    "{} is never closed",
+                    table.render(&Bracket::Open(*expected))
+                )
+            }
+        }
+    }
 
-            if lint_err.open_pos > 0 {
-                for _ in 0..lint_err.open_pos {
-                    print!(" ");
+    pub fn suggestion(&self, table: &PairTable) -> String {
+        match (&self.fix, &self.problem) {
+            (Fix::Insert { brackets, .. }, _) => {
+                let symbols: Vec<String> = brackets.iter().map(|b| table.render(b)).collect();
+
+                // Keywords like `end` need spaces between them, single characters don't
+                match symbols.iter().all(|s| s.chars().count() == 1) {
+                    true => format!("insert {}", symbols.concat()),
+                    false => format!("insert {}", symbols.join(" ")),
                 }
             }
-            print!("^");
-            for _ in 0..(lint_err.err_pos - lint_err.open_pos - 1) {
-                print!("-");
+            (
+                Fix::Delete { .. },
+                Problem::Mismatched { found, .. } | Problem::Unopened { found },
+            ) => format!("delete {}", table.render(&found.close())),
+            (Fix::Delete { .. }, Problem::Unclosed { expected, .. }) => {
+                format!("delete {}", table.render(&Bracket::Open(*expected)))
             }
-            println!(
-                "^-Expected {:?}, Found {:?}     {} + {}",
-                lint_err.expected, lint_err.found, score, line_score
-            );
+        }
+    }
 
-            score += line_score;
+    // First and last bracket the problem is about
+    pub fn span(&self) -> (usize, usize) {
+        match self.problem {
+            Problem::Mismatched { open_pos, .. } => (open_pos, self.pos),
+            Problem::Unopened { .. } => (self.pos, self.pos),
+            Problem::Unclosed { open_pos, .. } => (open_pos, open_pos),
         }
     }
 }
 
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let table = PairTable::standard();
+        let at = match self.fix {
+            Fix::Insert { pos, .. } | Fix::Delete { pos } => pos,
+        };
+
+        write!(
+            f,
+            "{}: {}: {} ({} at {})",
+            self.pos,
+            self.severity,
+            self.message(&table),
+            self.suggestion(&table),
+            at
+        )
+    }
+}
+
+// Unlike `lint_line` this carries on past the first bad closer. A closer that
+// belongs to an opener further down the stack pops back to that opener, which
+// means inserting the closers that were left out; a closer with no opener at all
+// is dropped. Openers still left at the end of the line are reported as warnings.
+pub fn lint_line_recovering(line: &[Bracket]) -> Vec<Diagnostic> {
+    let mut stack: Vec<(BracketShape, usize)> = vec![];
+    let mut diagnostics = vec![];
+
+    for (i, bracket) in line.iter().enumerate() {
+        let found = match bracket {
+            Bracket::Open(shape) => {
+                stack.push((*shape, i));
+                continue;
+            }
+            Bracket::Close(shape) => *shape,
+        };
+
+        let (expected, open_pos) = match stack.last() {
+            Some(&(shape, _)) if shape == found => {
+                stack.pop();
+                continue;
+            }
+            Some(&top) => top,
+            None => {
+                diagnostics.push(Diagnostic {
+                    problem: Problem::Unopened { found },
+                    pos: i,
+                    severity: Severity::Error,
+                    fix: Fix::Delete { pos: i },
+                });
+                continue;
+            }
+        };
+
+        let fix = match stack.iter().rposition(|(shape, _)| *shape == found) {
+            Some(matching) => {
+                let brackets = stack
+                    .drain(matching..)
+                    .skip(1)
+                    .rev()
+                    .map(|(shape, _)| shape.close())
+                    .collect();
+
+                Fix::Insert { pos: i, brackets }
+            }
+            None => Fix::Delete { pos: i },
+        };
+
+        diagnostics.push(Diagnostic {
+            problem: Problem::Mismatched {
+                expected,
+                found,
+                open_pos,
+            },
+            pos: i,
+            severity: Severity::Error,
+            fix,
+        });
+    }
+
+    for (expected, open_pos) in stack.into_iter().rev() {
+        diagnostics.push(Diagnostic {
+            problem: Problem::Unclosed { expected, open_pos },
+            pos: line.len(),
+            severity: Severity::Warning,
+            fix: Fix::Insert {
+                pos: line.len(),
+                brackets: vec![expected.close()],
+            },
+        });
+    }
+
+    diagnostics
+}
+
+pub fn apply_fixes(line: &[Bracket], diagnostics: &[Diagnostic]) -> Vec<Bracket> {
+    let mut fixed = vec![];
+
+    for i in 0..=line.len() {
+        let mut deleted = false;
+
+        for diagnostic in diagnostics {
+            match &diagnostic.fix {
+                Fix::Insert { pos, brackets } if *pos == i => fixed.extend(brackets),
+                Fix::Delete { pos } if *pos == i => deleted = true,
+                _ => {}
+            }
+        }
+
+        if let Some(bracket) = line.get(i) {
+            if !deleted {
+                fixed.push(*bracket);
+            }
+        }
+    }
+
+    fixed
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -470,4 +615,90 @@ mod tests {
 
         assert_eq!(middle_complete_score(&lines), 288957);
     }
+
+    #[test]
+    fn test_lint_recovering() {
+        let line = parse_line("{([(<{}[<>[]}>{[]{[(<()>");
+        let diagnostics = lint_line_recovering(&line);
+
+        assert_eq!(
+            diagnostics[0],
+            Diagnostic {
+                problem: Problem::Mismatched {
+                    expected: BracketShape::Square,
+                    found: BracketShape::Curly,
+                    open_pos: 7,
+                },
+                pos: 12,
+                severity: Severity::Error,
+                fix: Fix::Insert {
+                    pos: 12,
+                    brackets: parse_line("]>)])"),
+                },
+            }
+        );
+        assert_eq!(
+            diagnostics[1],
+            Diagnostic {
+                problem: Problem::Unopened {
+                    found: BracketShape::Angular
+                },
+                pos: 13,
+                severity: Severity::Error,
+                fix: Fix::Delete { pos: 13 },
+            }
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "12: error: expected ] to close [, found } (insert ]>)]) at 12)"
+        );
+
+        assert_eq!(parse_line("f(x) = a[0];"), parse_line("()[]"));
+        assert!(Bracket::try_from('x').is_err());
+
+        let line = parse_line("(]())");
+        let diagnostics = lint_line_recovering(&line);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].fix, Fix::Delete { pos: 1 });
+    }
+
+    #[test]
+    fn test_lint_recovering_finds_every_error() {
+        let lines = parse_lines(
+            "[({(<(())[]>[[{[]{<()<>>\n\
+                                                [(()[<>])]({[<{<<[]>>(\n\
+                                                {([(<{}[<>[]}>{[]{[(<()>\n\
+                                                (((({<>}<{<{<>}{[]{[]{}\n\
+                                                [[<[([]))<([[{}[[()]]]\n\
+                                                [{[{({}]{}}([{[{{{}}([]\n\
+                                                {<[[]]>}<{[{[{[]{()[[[]\n\
+                                                [<(<(<(<{}))><([]([]()\n\
+                                                <{([([[(<>()){}]>(<<{{\n\
+                                                <{([{{}}[<[[[<>{}]]]>[]]",
+        );
+
+        for line in &lines {
+            let diagnostics = lint_line_recovering(line);
+
+            if let Either::Right(err) = lint_line(line) {
+                assert_eq!(
+                    diagnostics[0].problem,
+                    Problem::Mismatched {
+                        expected: err.expected,
+                        found: err.found,
+                        open_pos: err.open_pos,
+                    }
+                );
+            } else {
+                assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+                assert_eq!(complete_line(line), Some(apply_fixes(line, &diagnostics)));
+            }
+
+            assert_eq!(
+                lint_line_recovering(&apply_fixes(line, &diagnostics)),
+                vec![]
+            );
+        }
+    }
 }
@@ -1,3 +1,5 @@
+use std::ops::RangeInclusive;
+
 pub struct Crabs {
     positions: Vec<u64>,
     max: u64,
@@ -24,11 +26,47 @@ pub enum FuelCalcMethod {
     Var,
 }
 
+impl FuelCalcMethod {
+    pub fn cost(&self, distance: u64) -> u64 {
+        match self {
+            FuelCalcMethod::Const => distance,
+            FuelCalcMethod::Var => distance * (distance + 1) / 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alignment {
+    pub position: u64,
+    pub fuel: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignmentReport {
+    pub range: RangeInclusive<u64>,
+    pub curve: Vec<(u64, u64)>,
+    pub optimal: Vec<u64>,
+    pub fuel: u64,
+    pub per_crab: Vec<(u64, u64)>,
+}
+
+impl AlignmentReport {
+    pub fn curve_csv(&self) -> String {
+        let mut csv = String::from("position,fuel\n");
+
+        for (position, fuel) in &self.curve {
+            csv.push_str(&format!("{},{}\n", position, fuel));
+        }
+
+        csv
+    }
+}
+
 impl Crabs {
     fn calc_distances_for(&self, target: u64) -> Vec<u64> {
         self.positions
             .iter()
-            .map(|pos| (*pos as i64 - target as i64).abs() as u64)
+            .map(|pos| pos.abs_diff(target))
             .collect()
     }
 
@@ -47,7 +85,7 @@ impl Crabs {
     pub fn test_all_const(&self, method: FuelCalcMethod) -> u64 {
         let mut cheapest = u64::MAX;
 
-        for i in 1..=self.max {
+        for i in 0..=self.max {
             let fuel = match method {
                 FuelCalcMethod::Const => self.calc_const_fuel_for(i),
                 FuelCalcMethod::Var => self.calc_var_fuel_for(i),
@@ -60,6 +98,325 @@ impl Crabs {
 
         cheapest
     }
+
+    pub fn total_fuel<F>(&self, target: u64, cost: F) -> u64
+    where
+        F: Fn(u64) -> u64,
+    {
+        self.positions
+            .iter()
+            .map(|pos| cost(pos.abs_diff(target)))
+            .sum()
+    }
+
+    // Where the optimum is known to lie for the built-in methods: the median for
+    // constant cost, and within half a step of the mean for the triangular cost
+    fn bounds_for(&self, method: &FuelCalcMethod) -> Option<(u64, u64)> {
+        let mut sorted = self.positions.clone();
+
+        sorted.sort_unstable();
+
+        let min = *sorted.first()?;
+        let max = *sorted.last()?;
+
+        match method {
+            FuelCalcMethod::Const => {
+                Some((sorted[(sorted.len() - 1) / 2], sorted[sorted.len() / 2]))
+            }
+            FuelCalcMethod::Var => {
+                let sum: u64 = sorted.iter().sum();
+                let count = sorted.len() as u64;
+                let floor = sum / count;
+
+                Some((floor.saturating_sub(1).max(min), (floor + 1).min(max)))
+            }
+        }
+    }
+
+    // Integer ternary search for the cheapest target within `lo..=hi`. Since the
+    // total is convex, comparing neighbours tells which side the minimum is on.
+    fn search<F>(&self, mut lo: u64, mut hi: u64, cost: F) -> Alignment
+    where
+        F: Fn(u64) -> u64,
+    {
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+
+            if self.total_fuel(mid, &cost) <= self.total_fuel(mid + 1, &cost) {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+
+        Alignment {
+            position: lo,
+            fuel: self.total_fuel(lo, &cost),
+        }
+    }
+
+    // Cheapest alignment for any cost that is convex and non-decreasing in distance
+    pub fn optimise<F>(&self, cost: F) -> Option<Alignment>
+    where
+        F: Fn(u64) -> u64,
+    {
+        let min = *self.positions.iter().min()?;
+        let max = *self.positions.iter().max()?;
+
+        Some(self.search(min, max, cost))
+    }
+
+    pub fn optimise_method(&self, method: &FuelCalcMethod) -> Option<Alignment> {
+        let (lo, hi) = self.bounds_for(method)?;
+
+        Some(self.search(lo, hi, |distance| method.cost(distance)))
+    }
+
+    // Fuel for every target in 0..=max, all targets tied for cheapest, and what
+    // each crab spends when moving to the first of them
+    pub fn report<F>(&self, cost: F) -> Option<AlignmentReport>
+    where
+        F: Fn(u64) -> u64,
+    {
+        if self.positions.is_empty() {
+            return None;
+        }
+
+        let range = 0..=self.max;
+        let curve: Vec<(u64, u64)> = range
+            .clone()
+            .map(|target| (target, self.total_fuel(target, &cost)))
+            .collect();
+        let fuel = curve.iter().map(|(_, fuel)| *fuel).min()?;
+        let optimal: Vec<u64> = curve
+            .iter()
+            .filter(|(_, f)| *f == fuel)
+            .map(|(target, _)| *target)
+            .collect();
+        let per_crab = self
+            .positions
+            .iter()
+            .map(|pos| (*pos, cost(pos.abs_diff(optimal[0]))))
+            .collect();
+
+        Some(AlignmentReport {
+            range,
+            curve,
+            optimal,
+            fuel,
+            per_crab,
+        })
+    }
+
+    // One weight per crab, in order; None if the counts differ
+    pub fn weighted(&self, weights: &[u64]) -> Option<Vec<WeightedCrab>> {
+        if weights.len() != self.positions.len() {
+            return None;
+        }
+
+        Some(
+            self.positions
+                .iter()
+                .zip(weights)
+                .map(|(position, weight)| WeightedCrab {
+                    position: *position,
+                    weight: *weight,
+                })
+                .collect(),
+        )
+    }
+
+    pub fn align_to(&self, k: usize, method: &FuelCalcMethod) -> Option<RallyPlan> {
+        let crabs: Vec<WeightedCrab> = self
+            .positions
+            .iter()
+            .map(|position| WeightedCrab {
+                position: *position,
+                weight: 1,
+            })
+            .collect();
+
+        align_to_k(&crabs, k, method)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeightedCrab {
+    pub position: u64,
+    pub weight: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RallyPlan {
+    pub targets: Vec<u64>,
+    pub assignments: Vec<usize>,
+    pub fuel: u64,
+}
+
+// Prefix sums over distinct sorted positions, so the weighted fuel of moving any
+// contiguous group to a target takes a binary search instead of a full pass
+struct Groups {
+    positions: Vec<i128>,
+    weight: Vec<i128>,
+    moment: Vec<i128>,
+    square: Vec<i128>,
+}
+
+impl Groups {
+    fn new(crabs: &[WeightedCrab]) -> Self {
+        let mut merged: Vec<(u64, u64)> = vec![];
+        let mut sorted = crabs.to_vec();
+
+        sorted.sort_unstable_by_key(|crab| crab.position);
+
+        for crab in sorted {
+            match merged.last_mut() {
+                Some((position, weight)) if *position == crab.position => *weight += crab.weight,
+                _ => merged.push((crab.position, crab.weight)),
+            }
+        }
+
+        let mut groups = Groups {
+            positions: vec![],
+            weight: vec![0],
+            moment: vec![0],
+            square: vec![0],
+        };
+
+        for (position, weight) in merged {
+            let (p, w) = (position as i128, weight as i128);
+
+            groups.positions.push(p);
+            groups.weight.push(groups.weight.last().unwrap() + w);
+            groups.moment.push(groups.moment.last().unwrap() + w * p);
+            groups
+                .square
+                .push(groups.square.last().unwrap() + w * p * p);
+        }
+
+        groups
+    }
+
+    fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    // Fuel for moving groups from..to (exclusive) to `target`
+    fn fuel(&self, from: usize, to: usize, target: i128, method: &FuelCalcMethod) -> i128 {
+        let split = from + self.positions[from..to].partition_point(|p| *p < target);
+        let sum = |prefix: &Vec<i128>, a: usize, b: usize| prefix[b] - prefix[a];
+
+        let linear = target * sum(&self.weight, from, split) - sum(&self.moment, from, split)
+            + sum(&self.moment, split, to)
+            - target * sum(&self.weight, split, to);
+
+        match method {
+            FuelCalcMethod::Const => linear,
+            FuelCalcMethod::Var => {
+                let squared = sum(&self.square, from, to)
+                    - 2 * target * sum(&self.moment, from, to)
+                    + target * target * sum(&self.weight, from, to);
+
+                (squared + linear) / 2
+            }
+        }
+    }
+
+    // Cheapest single rally point for groups from..to, see `Crabs::search`
+    fn rally(&self, from: usize, to: usize, method: &FuelCalcMethod) -> (i128, i128) {
+        let (mut lo, mut hi) = (self.positions[from], self.positions[to - 1]);
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+
+            if self.fuel(from, to, mid, method) <= self.fuel(from, to, mid + 1, method) {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+
+        (lo, self.fuel(from, to, lo, method))
+    }
+}
+
+// Splits the crabs over at most `k` rally points. Every crab heads for its nearest
+// rally point, so in 1D each rally point serves a contiguous run of sorted positions,
+// and the best split is found exactly by DP over those runs.
+pub fn align_to_k(crabs: &[WeightedCrab], k: usize, method: &FuelCalcMethod) -> Option<RallyPlan> {
+    let groups = Groups::new(crabs);
+    let n = groups.len();
+    let k = k.min(n);
+
+    if k == 0 {
+        return None;
+    }
+
+    let mut rally = vec![vec![(0i128, 0i128); n + 1]; n];
+
+    for (from, row) in rally.iter_mut().enumerate() {
+        for (to, cell) in row.iter_mut().enumerate().skip(from + 1) {
+            *cell = groups.rally(from, to, method);
+        }
+    }
+
+    // best[j][i]: cheapest way to serve the first i groups with j rally points
+    let mut best = vec![vec![i128::MAX; n + 1]; k + 1];
+    let mut split = vec![vec![0usize; n + 1]; k + 1];
+
+    best[0][0] = 0;
+
+    for j in 1..=k {
+        for i in j..=n {
+            for m in (j - 1)..i {
+                if best[j - 1][m] == i128::MAX {
+                    continue;
+                }
+
+                let fuel = best[j - 1][m] + rally[m][i].1;
+
+                if fuel < best[j][i] {
+                    best[j][i] = fuel;
+                    split[j][i] = m;
+                }
+            }
+        }
+    }
+
+    let mut targets = vec![];
+    let mut group_target = vec![0usize; n];
+    let mut i = n;
+
+    for j in (1..=k).rev() {
+        let m = split[j][i];
+
+        targets.push(rally[m][i].0 as u64);
+
+        for target in group_target.iter_mut().take(i).skip(m) {
+            *target = j - 1;
+        }
+
+        i = m;
+    }
+
+    targets.reverse();
+
+    let assignments = crabs
+        .iter()
+        .map(|crab| {
+            let group = groups
+                .positions
+                .partition_point(|p| *p < crab.position as i128);
+
+            group_target[group]
+        })
+        .collect();
+
+    Some(RallyPlan {
+        targets,
+        assignments,
+        fuel: best[k][n] as u64,
+    })
 }
 
 #[cfg(test)]
@@ -73,4 +430,170 @@ mod tests {
         assert_eq!(crabs.test_all_const(FuelCalcMethod::Const), 37);
         assert_eq!(crabs.test_all_const(FuelCalcMethod::Var), 168);
     }
+
+    #[test]
+    fn test_position_zero() {
+        let crabs = Crabs::from("0,0,0,5");
+
+        assert_eq!(crabs.test_all_const(FuelCalcMethod::Const), 5);
+        assert_eq!(
+            crabs.optimise_method(&FuelCalcMethod::Const),
+            Some(Alignment {
+                position: 0,
+                fuel: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_optimise() {
+        let crabs = Crabs::from("16,1,2,0,4,2,7,1,2,14");
+
+        assert_eq!(
+            crabs.optimise_method(&FuelCalcMethod::Const),
+            Some(Alignment {
+                position: 2,
+                fuel: 37
+            })
+        );
+        assert_eq!(
+            crabs.optimise_method(&FuelCalcMethod::Var),
+            Some(Alignment {
+                position: 5,
+                fuel: 168
+            })
+        );
+        assert_eq!(
+            crabs.optimise(|distance| FuelCalcMethod::Var.cost(distance)),
+            crabs.optimise_method(&FuelCalcMethod::Var)
+        );
+        assert_eq!(Crabs::from("").optimise(|distance| distance), None);
+    }
+
+    #[test]
+    fn test_optimise_custom_cost() {
+        let crabs = Crabs::from("16,1,2,0,4,2,7,1,2,14,30,3");
+        let cost = |distance: u64| distance.pow(3);
+
+        let brute = (0..=30)
+            .map(|target| crabs.total_fuel(target, cost))
+            .min()
+            .unwrap();
+
+        assert_eq!(crabs.optimise(cost).unwrap().fuel, brute);
+    }
+
+    #[test]
+    fn test_report() {
+        let crabs = Crabs::from("16,1,2,0,4,2,7,1,2,14");
+        let report = crabs.report(|distance| distance).unwrap();
+
+        assert_eq!(report.range, 0..=16);
+        assert_eq!(report.curve.len(), 17);
+        assert_eq!(report.curve[1], (1, 41));
+        assert_eq!(report.curve[3], (3, 39));
+        assert_eq!(report.curve[10], (10, 71));
+        assert_eq!(report.optimal, vec![2]);
+        assert_eq!(report.fuel, 37);
+        assert_eq!(report.per_crab[0], (16, 14));
+        assert_eq!(
+            report.per_crab.iter().map(|(_, fuel)| fuel).sum::<u64>(),
+            37
+        );
+
+        let report = crabs.report(|d| FuelCalcMethod::Var.cost(d)).unwrap();
+
+        assert_eq!(report.optimal, vec![5]);
+        assert_eq!(report.curve[2], (2, 206));
+    }
+
+    #[test]
+    fn test_report_ties() {
+        let report = Crabs::from("0,4").report(|distance| distance).unwrap();
+
+        assert_eq!(report.optimal, vec![0, 1, 2, 3, 4]);
+        assert_eq!(report.per_crab, vec![(0, 0), (4, 4)]);
+        assert_eq!(
+            report.curve_csv(),
+            "position,fuel\n0,4\n1,4\n2,4\n3,4\n4,4\n"
+        );
+    }
+
+    #[test]
+    fn test_align_to_one() {
+        let crabs = Crabs::from("16,1,2,0,4,2,7,1,2,14");
+
+        let plan = crabs.align_to(1, &FuelCalcMethod::Const).unwrap();
+
+        assert_eq!(plan.targets, vec![2]);
+        assert_eq!(plan.fuel, 37);
+        assert_eq!(plan.assignments, vec![0; 10]);
+
+        let plan = crabs.align_to(1, &FuelCalcMethod::Var).unwrap();
+
+        assert_eq!(plan.targets, vec![5]);
+        assert_eq!(plan.fuel, 168);
+    }
+
+    #[test]
+    fn test_align_to_k() {
+        let crabs = Crabs::from("1,12,2,3,10,11");
+        let plan = crabs.align_to(2, &FuelCalcMethod::Const).unwrap();
+
+        assert_eq!(plan.targets, vec![2, 11]);
+        assert_eq!(plan.assignments, vec![0, 1, 0, 0, 1, 1]);
+        assert_eq!(plan.fuel, 4);
+
+        let plan = crabs.align_to(10, &FuelCalcMethod::Var).unwrap();
+
+        assert_eq!(plan.targets, vec![1, 2, 3, 10, 11, 12]);
+        assert_eq!(plan.fuel, 0);
+        assert_eq!(crabs.align_to(0, &FuelCalcMethod::Var), None);
+    }
+
+    #[test]
+    fn test_align_weighted() {
+        let crabs = Crabs::from("0,10");
+        let plan =
+            align_to_k(&crabs.weighted(&[1, 5]).unwrap(), 1, &FuelCalcMethod::Const).unwrap();
+
+        assert_eq!(plan.targets, vec![10]);
+        assert_eq!(plan.fuel, 10);
+        assert_eq!(crabs.weighted(&[1]), None);
+        assert_eq!(crabs.weighted(&[1, 5, 2]), None);
+
+        let crabs = Crabs::from("0,4");
+        let plan = align_to_k(&crabs.weighted(&[1, 3]).unwrap(), 1, &FuelCalcMethod::Var).unwrap();
+
+        assert_eq!(plan.targets, vec![3]);
+        assert_eq!(plan.fuel, 9);
+    }
+
+    #[test]
+    fn test_align_against_brute_force() {
+        let crabs = Crabs::from("16,1,2,0,4,2,7,1,2,14");
+        let weighted = crabs.weighted(&[3, 1, 4, 1, 5, 9, 2, 6, 5, 3]).unwrap();
+
+        for method in [FuelCalcMethod::Const, FuelCalcMethod::Var] {
+            let mut brute = u64::MAX;
+
+            for a in 0..=16 {
+                for b in a..=16 {
+                    let fuel = weighted
+                        .iter()
+                        .map(|crab| {
+                            crab.weight
+                                * method
+                                    .cost(crab.position.abs_diff(a))
+                                    .min(method.cost(crab.position.abs_diff(b)))
+                        })
+                        .sum();
+
+                    brute = brute.min(fuel);
+                }
+            }
+
+            assert_eq!(align_to_k(&weighted, 2, &method).unwrap().fuel, brute);
+        }
+    }
 }
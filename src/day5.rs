@@ -2,7 +2,7 @@ use crate::vents::*;
 
 #[aoc_generator(day5)]
 pub fn input_generator(input: &str) -> Map {
-    Map::from(input)
+    Map::try_from(input).unwrap()
 }
 
 #[aoc(day5, part1)]
@@ -12,5 +12,5 @@ pub fn part1(entries: &Entries) -> usize {
 
 #[aoc(day8, part2)]
 pub fn part2(entries: &Entries) -> u64 {
-    entries.count_real_output().unwrap()
+    entries.sum_deduced_output().unwrap()
 }
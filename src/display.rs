@@ -0,0 +1,492 @@
+use crate::segment::DecodeError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayDefinition {
+    // Letter naming each real segment, scrambled input uses the same letters
+    pub segments: Vec<char>,
+    // Glyph shown when exactly these real segments are lit
+    pub glyphs: Vec<(char, u32)>,
+}
+
+// Scrambled segment index -> real segment index
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rewiring {
+    pub real_for: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedGlyphs {
+    pub glyphs: String,
+    pub wirings: usize,
+}
+
+const SEVEN_SEGMENT_GLYPHS: [(char, &str); 16] = [
+    ('0', "abcefg"),
+    ('1', "cf"),
+    ('2', "acdeg"),
+    ('3', "acdfg"),
+    ('4', "bcdf"),
+    ('5', "abdfg"),
+    ('6', "abdefg"),
+    ('7', "acf"),
+    ('8', "abcdefg"),
+    ('9', "abcdfg"),
+    ('A', "abcdef"),
+    ('b', "bdefg"),
+    ('C', "abeg"),
+    ('d', "cdefg"),
+    ('E', "abdeg"),
+    ('F', "abde"),
+];
+
+const FOURTEEN_SEGMENT_GLYPHS: [(char, &str); 36] = [
+    ('0', "abcdefkl"),
+    ('1', "bc"),
+    ('2', "abdegh"),
+    ('3', "abcdh"),
+    ('4', "bcfgh"),
+    ('5', "acdfgh"),
+    ('6', "acdefgh"),
+    ('7', "abc"),
+    ('8', "abcdefgh"),
+    ('9', "abcdfgh"),
+    ('A', "abcefgh"),
+    ('B', "abcdhjm"),
+    ('C', "adef"),
+    ('D', "abcdjm"),
+    ('E', "adefg"),
+    ('F', "aefg"),
+    ('G', "acdefh"),
+    ('H', "bcefgh"),
+    ('I', "adjm"),
+    ('J', "bcde"),
+    ('K', "efgkn"),
+    ('L', "def"),
+    ('M', "bcefik"),
+    ('N', "bcefin"),
+    ('O', "abcdef"),
+    ('P', "abefgh"),
+    ('Q', "abcdefn"),
+    ('R', "abefghn"),
+    ('S', "acdhi"),
+    ('T', "ajm"),
+    ('U', "bcdef"),
+    ('V', "efkl"),
+    ('W', "bcefln"),
+    ('X', "ikln"),
+    ('Y', "ikm"),
+    ('Z', "adkl"),
+];
+
+impl DisplayDefinition {
+    // Segment masks are u32s, so a display has at most 31 segments. Segment
+    // letters and glyph patterns have to be unique for decoding to be too.
+    pub fn new(segments: &str, glyphs: &[(char, &str)]) -> Result<Self, DecodeError> {
+        let mut definition = DisplayDefinition {
+            segments: segments.chars().collect(),
+            glyphs: vec![],
+        };
+
+        if definition.segments.len() >= 32 {
+            return Err(DecodeError::TooManySegments(definition.segments.len()));
+        }
+
+        for (i, letter) in definition.segments.iter().enumerate() {
+            if definition.segments[..i].contains(letter) {
+                return Err(DecodeError::DuplicateSegment(*letter));
+            }
+        }
+
+        for (glyph, lit) in glyphs {
+            let mask = definition.pattern(lit)?;
+
+            if let Some(other) = definition.glyph(mask) {
+                return Err(DecodeError::SharedPattern(other, *glyph));
+            }
+
+            definition.glyphs.push((*glyph, mask));
+        }
+
+        Ok(definition)
+    }
+
+    //  aaaa
+    // b    c
+    //  dddd
+    // e    f
+    //  gggg
+    pub fn seven_segment_decimal() -> Self {
+        DisplayDefinition::new("abcdefg", &SEVEN_SEGMENT_GLYPHS[..10])
+            .expect("Invalid built-in display")
+    }
+
+    pub fn seven_segment_hex() -> Self {
+        DisplayDefinition::new("abcdefg", &SEVEN_SEGMENT_GLYPHS).expect("Invalid built-in display")
+    }
+
+    //  aaaaa
+    // fi j kb
+    // f ijk b
+    //  gg hh
+    // e lmn c
+    // el m nc
+    //  ddddd
+    pub fn fourteen_segment() -> Self {
+        DisplayDefinition::new("abcdefghijklmn", &FOURTEEN_SEGMENT_GLYPHS)
+            .expect("Invalid built-in display")
+    }
+
+    // As the 14-segment display, with the top and bottom bars split into a|o and d|p
+    pub fn sixteen_segment() -> Self {
+        let glyphs: Vec<(char, String)> = FOURTEEN_SEGMENT_GLYPHS
+            .iter()
+            .map(|(glyph, lit)| (*glyph, lit.replace('a', "ao").replace('d', "dp")))
+            .collect();
+
+        DisplayDefinition::new(
+            "abcdefghijklmnop",
+            &glyphs
+                .iter()
+                .map(|(glyph, lit)| (*glyph, lit.as_str()))
+                .collect::<Vec<(char, &str)>>(),
+        )
+        .expect("Invalid built-in display")
+    }
+
+    pub fn pattern(&self, letters: &str) -> Result<u32, DecodeError> {
+        letters.chars().try_fold(0u32, |mask, letter| {
+            let i = self
+                .segments
+                .iter()
+                .position(|s| *s == letter)
+                .ok_or(DecodeError::UnknownSegment(letter))?;
+
+            Ok(mask | (1 << i))
+        })
+    }
+
+    pub fn glyph(&self, mask: u32) -> Option<char> {
+        self.glyphs
+            .iter()
+            .find(|(_, lit)| *lit == mask)
+            .map(|(glyph, _)| *glyph)
+    }
+
+    pub fn letters(&self, mask: u32) -> String {
+        self.segments
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| mask & (1 << i) != 0)
+            .map(|(_, letter)| *letter)
+            .collect()
+    }
+
+    // Whether some glyph of the same size can still be shown by `pattern`, given
+    // the scrambled segments assigned so far
+    fn placeable(&self, pattern: u32, real_for: &[Option<usize>]) -> bool {
+        let mut lit = 0u32;
+        let mut dark = 0u32;
+
+        for (scrambled, real) in real_for.iter().enumerate() {
+            if let Some(real) = real {
+                match pattern & (1 << scrambled) != 0 {
+                    true => lit |= 1 << real,
+                    false => dark |= 1 << real,
+                }
+            }
+        }
+
+        self.glyphs.iter().any(|(_, glyph)| {
+            glyph.count_ones() == pattern.count_ones() && glyph & lit == lit && glyph & dark == 0
+        })
+    }
+
+    // Every rewiring under which all patterns show a glyph. Each scrambled segment
+    // starts out with the real segments allowed by the glyphs its patterns could be,
+    // then the most constrained one is assigned first and the rest backtracked.
+    pub fn solve(&self, patterns: &[u32]) -> Vec<Rewiring> {
+        let n = self.segments.len();
+        let mut candidates = vec![(1u32 << n) - 1; n];
+
+        for pattern in patterns {
+            let sized = self
+                .glyphs
+                .iter()
+                .filter(|(_, glyph)| glyph.count_ones() == pattern.count_ones());
+            let (lit, dark) = sized.fold((0u32, 0u32), |(lit, dark), (_, glyph)| {
+                (lit | glyph, dark | !glyph)
+            });
+
+            for (scrambled, allowed) in candidates.iter_mut().enumerate() {
+                match pattern & (1 << scrambled) != 0 {
+                    true => *allowed &= lit,
+                    false => *allowed &= dark,
+                }
+            }
+        }
+
+        let mut order: Vec<usize> = (0..n).collect();
+
+        order.sort_by_key(|scrambled| candidates[*scrambled].count_ones());
+
+        let mut solutions = vec![];
+
+        self.assign(
+            patterns,
+            &candidates,
+            &order,
+            &mut vec![None; n],
+            0,
+            &mut solutions,
+        );
+
+        solutions
+    }
+
+    fn assign(
+        &self,
+        patterns: &[u32],
+        candidates: &[u32],
+        order: &[usize],
+        real_for: &mut Vec<Option<usize>>,
+        used: u32,
+        solutions: &mut Vec<Rewiring>,
+    ) {
+        let scrambled = match order.first() {
+            Some(scrambled) => *scrambled,
+            None => {
+                solutions.push(Rewiring {
+                    real_for: real_for.iter().map(|real| real.unwrap()).collect(),
+                });
+                return;
+            }
+        };
+
+        for real in 0..self.segments.len() {
+            if candidates[scrambled] & (1 << real) == 0 || used & (1 << real) != 0 {
+                continue;
+            }
+
+            real_for[scrambled] = Some(real);
+
+            if patterns.iter().all(|p| self.placeable(*p, real_for)) {
+                self.assign(
+                    patterns,
+                    candidates,
+                    &order[1..],
+                    real_for,
+                    used | (1 << real),
+                    solutions,
+                );
+            }
+
+            real_for[scrambled] = None;
+        }
+    }
+
+    // As `solve`, but when nothing fits, names the first pattern after which no
+    // rewiring is left
+    pub fn solve_or_blame(&self, patterns: &[u32]) -> Result<Vec<Rewiring>, DecodeError> {
+        let wirings = self.solve(patterns);
+
+        if !wirings.is_empty() {
+            return Ok(wirings);
+        }
+
+        let culprit = (1..=patterns.len())
+            .find(|len| self.solve(&patterns[..*len]).is_empty())
+            .map(|len| patterns[len - 1])
+            .unwrap_or(0);
+
+        Err(DecodeError::UnplaceablePattern(self.letters(culprit)))
+    }
+
+    // Decodes an entry line in the puzzle format, "<patterns> | <output>"
+    pub fn decode(&self, line: &str) -> Result<DecodedGlyphs, DecodeError> {
+        let (patterns, output) = line.split_once(" | ").unwrap_or((line, ""));
+        let patterns = patterns
+            .split_whitespace()
+            .map(|p| self.pattern(p))
+            .collect::<Result<Vec<u32>, DecodeError>>()?;
+        let output = output
+            .split_whitespace()
+            .map(|p| self.pattern(p))
+            .collect::<Result<Vec<u32>, DecodeError>>()?;
+        let all: Vec<u32> = patterns.iter().chain(output.iter()).copied().collect();
+
+        let wirings = self.solve_or_blame(&all)?;
+
+        let mut decoded: Vec<String> = wirings
+            .iter()
+            .map(|wiring| {
+                output
+                    .iter()
+                    .filter_map(|p| self.glyph(wiring.apply(*p)))
+                    .collect()
+            })
+            .collect();
+
+        decoded.sort_unstable();
+        decoded.dedup();
+
+        match decoded.len() {
+            1 => Ok(DecodedGlyphs {
+                glyphs: decoded.remove(0),
+                wirings: wirings.len(),
+            }),
+            _ => Err(DecodeError::AmbiguousGlyphs(decoded)),
+        }
+    }
+}
+
+impl Rewiring {
+    pub fn apply(&self, pattern: u32) -> u32 {
+        self.real_for
+            .iter()
+            .enumerate()
+            .filter(|(scrambled, _)| pattern & (1 << scrambled) != 0)
+            .fold(0, |real, (_, r)| real | (1 << r))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scramble(definition: &DisplayDefinition, wiring: &str, glyphs: &str) -> String {
+        // `wiring` lists the scrambled letter driving each real segment
+        let wiring: Vec<char> = wiring.chars().collect();
+
+        glyphs
+            .chars()
+            .map(|glyph| {
+                let (_, mask) = definition.glyphs.iter().find(|(g, _)| *g == glyph).unwrap();
+
+                definition
+                    .letters(*mask)
+                    .chars()
+                    .map(|real| {
+                        wiring[definition.segments.iter().position(|s| *s == real).unwrap()]
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn test_distinct_glyphs() {
+        for definition in [
+            DisplayDefinition::seven_segment_decimal(),
+            DisplayDefinition::seven_segment_hex(),
+            DisplayDefinition::fourteen_segment(),
+            DisplayDefinition::sixteen_segment(),
+        ] {
+            let mut masks: Vec<u32> = definition.glyphs.iter().map(|(_, m)| *m).collect();
+
+            masks.sort_unstable();
+            masks.dedup();
+
+            assert_eq!(masks.len(), definition.glyphs.len());
+        }
+    }
+
+    #[test]
+    fn test_seven_segment() {
+        let definition = DisplayDefinition::seven_segment_decimal();
+
+        assert_eq!(
+            definition.decode(
+                "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf"
+            ),
+            Ok(DecodedGlyphs {
+                glyphs: "5353".to_string(),
+                wirings: 1
+            })
+        );
+        assert_eq!(
+            definition.decode("ab abc abcd | abcd ab"),
+            Err(DecodeError::UnplaceablePattern("abcd".to_string()))
+        );
+        assert_eq!(
+            definition.decode("ab abx | ab"),
+            Err(DecodeError::UnknownSegment('x'))
+        );
+    }
+
+    #[test]
+    fn test_seven_segment_hex() {
+        let definition = DisplayDefinition::seven_segment_hex();
+        let line = format!(
+            "{} | {}",
+            scramble(&definition, "gfedcba", "0123456789AbCdEF"),
+            scramble(&definition, "gfedcba", "C0FFEE")
+        );
+
+        assert_eq!(
+            definition.decode(&line),
+            Ok(DecodedGlyphs {
+                glyphs: "C0FFEE".to_string(),
+                wirings: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_fourteen_segment() {
+        let definition = DisplayDefinition::fourteen_segment();
+        let wiring = "dkabnmlcjigfhe";
+        let line = format!(
+            "{} | {}",
+            scramble(&definition, wiring, "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+            scramble(&definition, wiring, "HELLOW0RLD")
+        );
+
+        let decoded = definition.decode(&line).unwrap();
+
+        assert_eq!(decoded.glyphs, "HELLOW0RLD");
+        assert_eq!(decoded.wirings, 1);
+    }
+
+    #[test]
+    fn test_sixteen_segment() {
+        let definition = DisplayDefinition::sixteen_segment();
+        let wiring = "pondmlkjihgfecba";
+        let line = format!(
+            "{} | {}",
+            scramble(&definition, wiring, "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+            scramble(&definition, wiring, "RUST2021")
+        );
+
+        let decoded = definition.decode(&line).unwrap();
+
+        // The halves of the top and bottom bars always light together, so they
+        // can be swapped without changing any glyph
+        assert_eq!(decoded.glyphs, "RUST2021");
+        assert_eq!(decoded.wirings, 4);
+    }
+
+    #[test]
+    fn test_invalid_definition() {
+        assert_eq!(
+            DisplayDefinition::new("abc", &[('1', "ab"), ('2', "abz")]),
+            Err(DecodeError::UnknownSegment('z'))
+        );
+
+        let segments: String = ('A'..='Z').chain('a'..='f').collect();
+
+        assert_eq!(
+            DisplayDefinition::new(&segments, &[]),
+            Err(DecodeError::TooManySegments(32))
+        );
+        assert!(DisplayDefinition::new(&segments[1..], &[('x', "Bf")]).is_ok());
+        assert_eq!(
+            DisplayDefinition::new("abca", &[]),
+            Err(DecodeError::DuplicateSegment('a'))
+        );
+        assert_eq!(
+            DisplayDefinition::new("abc", &[('1', "ab"), ('2', "c"), ('7', "ba")]),
+            Err(DecodeError::SharedPattern('1', '7'))
+        );
+    }
+}
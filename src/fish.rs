@@ -44,6 +44,303 @@ pub fn count_fishes(waters: &FishyWaters, day_count: usize) -> u64 {
     state.iter().sum()
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Species {
+    pub reset_timer: usize,
+    pub newborn_timer: usize,
+    pub offspring: u128,
+    pub mortality_age: Option<usize>,
+}
+
+impl Species {
+    pub fn lanternfish() -> Self {
+        Species {
+            reset_timer: 6,
+            newborn_timer: 8,
+            offspring: 1,
+            mortality_age: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Population {
+    pub species: Species,
+    // Fish count per [age][timer], ages are only tracked when the species can die
+    pub buckets: Vec<Vec<u128>>,
+}
+
+impl Population {
+    pub fn new(species: Species, waters: &FishyWaters) -> Self {
+        let timer_count = waters
+            .days
+            .len()
+            .max(species.reset_timer + 1)
+            .max(species.newborn_timer + 1);
+        let age_count = species.mortality_age.unwrap_or(1).max(1);
+
+        let mut buckets = vec![vec![0u128; timer_count]; age_count];
+
+        for (timer, count) in waters.days.iter().enumerate() {
+            buckets[0][timer] = *count as u128;
+        }
+
+        Population { species, buckets }
+    }
+
+    // None once a count no longer fits in a u128
+    pub fn tick(&self) -> Option<Population> {
+        let mut wip = vec![vec![0u128; self.buckets[0].len()]; self.buckets.len()];
+        let ages_tracked = self.species.mortality_age.is_some();
+
+        for (age, timers) in self.buckets.iter().enumerate() {
+            let next_age = match ages_tracked {
+                true => age + 1,
+                false => 0,
+            };
+
+            for (timer, count) in timers.iter().enumerate() {
+                if *count == 0 {
+                    continue;
+                }
+
+                let next_timer = match timer {
+                    0 => {
+                        let newborn = &mut wip[0][self.species.newborn_timer];
+
+                        *newborn =
+                            newborn.checked_add(count.checked_mul(self.species.offspring)?)?;
+
+                        self.species.reset_timer
+                    }
+                    _ => timer - 1,
+                };
+
+                if next_age < wip.len() {
+                    wip[next_age][next_timer] = wip[next_age][next_timer].checked_add(*count)?;
+                }
+            }
+        }
+
+        Some(Population {
+            species: self.species,
+            buckets: wip,
+        })
+    }
+
+    pub fn timers(&self) -> Option<Vec<u128>> {
+        (0..self.buckets[0].len())
+            .map(|timer| {
+                self.buckets
+                    .iter()
+                    .try_fold(0u128, |sum, ages| sum.checked_add(ages[timer]))
+            })
+            .collect()
+    }
+
+    pub fn total(&self) -> Option<u128> {
+        self.buckets
+            .iter()
+            .flatten()
+            .try_fold(0u128, |sum, count| sum.checked_add(*count))
+    }
+}
+
+// None once the population no longer fits in a u128
+pub fn simulate(species: Species, waters: &FishyWaters, day_count: usize) -> Option<u128> {
+    let mut population = Population::new(species, waters);
+
+    for _ in 0..day_count {
+        population = population.tick()?;
+    }
+
+    population.total()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayStats {
+    pub day: usize,
+    pub timers: Vec<u128>,
+    pub total: u128,
+    pub growth: Option<f64>,
+}
+
+// An overflowed day is never committed, so every later call ends there too
+pub struct History {
+    population: Population,
+    day: usize,
+    previous_total: Option<u128>,
+}
+
+impl From<Population> for History {
+    fn from(population: Population) -> Self {
+        History {
+            population,
+            day: 0,
+            previous_total: None,
+        }
+    }
+}
+
+impl Iterator for History {
+    type Item = DayStats;
+
+    // Yields day 0 (the initial state) first, then one entry per simulated day,
+    // and ends on the first day whose counts no longer fit in a u128
+    fn next(&mut self) -> Option<Self::Item> {
+        let population = match self.day {
+            0 => self.population.clone(),
+            _ => self.population.tick()?,
+        };
+        let total = population.total()?;
+        let stats = DayStats {
+            day: self.day,
+            timers: population.timers()?,
+            total,
+            growth: self
+                .previous_total
+                .filter(|previous| *previous > 0)
+                .map(|previous| total as f64 / previous as f64),
+        };
+
+        self.population = population;
+        self.day += 1;
+        self.previous_total = Some(total);
+
+        Some(stats)
+    }
+}
+
+pub fn history_csv(history: impl Iterator<Item = DayStats>) -> String {
+    let mut csv = String::new();
+    let mut header_written = false;
+
+    for stats in history {
+        if !header_written {
+            let timers = (0..stats.timers.len())
+                .map(|timer| format!("timer_{}", timer))
+                .collect::<Vec<String>>()
+                .join(",");
+
+            csv.push_str(&format!("day,{},total,growth\n", timers));
+            header_written = true;
+        }
+
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            stats.day,
+            stats
+                .timers
+                .iter()
+                .map(|count| count.to_string())
+                .collect::<Vec<String>>()
+                .join(","),
+            stats.total,
+            stats
+                .growth
+                .map(|growth| format!("{:.6}", growth))
+                .unwrap_or_default()
+        ));
+    }
+
+    csv
+}
+
+type Matrix = [[u128; 9]; 9];
+
+// Row is tomorrow's timer, column is today's timer
+fn transition_matrix() -> Matrix {
+    let mut matrix = [[0u128; 9]; 9];
+
+    for timer in 0..8 {
+        matrix[timer][timer + 1] = 1;
+    }
+
+    matrix[6][0] = 1;
+    matrix[8][0] = 1;
+
+    matrix
+}
+
+fn identity_matrix() -> Matrix {
+    let mut matrix = [[0u128; 9]; 9];
+
+    for (i, row) in matrix.iter_mut().enumerate() {
+        row[i] = 1;
+    }
+
+    matrix
+}
+
+fn reduce(value: u128, modulus: Option<u128>) -> u128 {
+    match modulus {
+        Some(m) => value % m,
+        None => value,
+    }
+}
+
+fn mul_matrix(a: &Matrix, b: &Matrix, modulus: Option<u128>) -> Option<Matrix> {
+    let mut product = [[0u128; 9]; 9];
+
+    for (row, a_row) in a.iter().enumerate() {
+        for col in 0..9 {
+            let mut sum = 0u128;
+
+            for (k, a_value) in a_row.iter().enumerate() {
+                let term = reduce(a_value.checked_mul(b[k][col])?, modulus);
+
+                sum = reduce(sum.checked_add(term)?, modulus);
+            }
+
+            product[row][col] = sum;
+        }
+    }
+
+    Some(product)
+}
+
+// Same population as `count_fishes`, but found by repeated squaring of the daily
+// transition, so it takes O(log day_count) steps. Without a modulus it returns None
+// once the count no longer fits in a u128, and a modulus of zero is always None.
+pub fn count_fishes_pow(
+    waters: &FishyWaters,
+    day_count: u64,
+    modulus: Option<u64>,
+) -> Option<u128> {
+    if modulus == Some(0) {
+        return None;
+    }
+
+    let modulus = modulus.map(|m| m as u128);
+    let mut result = identity_matrix();
+    let mut square = transition_matrix();
+    let mut exp = day_count;
+
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_matrix(&result, &square, modulus)?;
+        }
+
+        exp >>= 1;
+
+        if exp > 0 {
+            square = mul_matrix(&square, &square, modulus)?;
+        }
+    }
+
+    let mut total = 0u128;
+
+    for row in &result {
+        for (timer, count) in waters.days.iter().enumerate() {
+            let term = reduce(row[timer].checked_mul(*count as u128)?, modulus);
+
+            total = reduce(total.checked_add(term)?, modulus);
+        }
+    }
+
+    Some(total)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -55,4 +352,116 @@ mod tests {
         assert_eq!(count_fishes(&waters, 18), 26);
         assert_eq!(count_fishes(&waters, 80), 5934);
     }
+
+    #[test]
+    fn test_lanternfish_preset() {
+        let waters = FishyWaters::from("3,4,3,1,2");
+
+        for day_count in [18, 80, 256] {
+            assert_eq!(
+                simulate(Species::lanternfish(), &waters, day_count),
+                Some(count_fishes(&waters, day_count) as u128)
+            );
+        }
+
+        assert!(simulate(Species::lanternfish(), &waters, 600).unwrap() > u64::MAX as u128);
+        assert_eq!(simulate(Species::lanternfish(), &waters, 1100), None);
+    }
+
+    #[test]
+    fn test_mortality() {
+        let species = Species {
+            reset_timer: 1,
+            newborn_timer: 1,
+            offspring: 2,
+            mortality_age: Some(3),
+        };
+        let mut population = Population::new(species, &FishyWaters::from("0"));
+        let mut totals = vec![];
+
+        for _ in 0..4 {
+            population = population.tick().unwrap();
+            totals.push(population.total().unwrap());
+        }
+
+        assert_eq!(totals, vec![3, 3, 8, 6]);
+    }
+
+    #[test]
+    fn test_count_fishes_pow() {
+        let waters = FishyWaters::from("3,4,3,1,2");
+
+        assert_eq!(count_fishes_pow(&waters, 0, None), Some(5));
+        assert_eq!(count_fishes_pow(&waters, 18, None), Some(26));
+        assert_eq!(
+            count_fishes_pow(&waters, 80, None),
+            Some(count_fishes(&waters, 80) as u128)
+        );
+        assert_eq!(
+            count_fishes_pow(&waters, 256, None),
+            Some(count_fishes(&waters, 256) as u128)
+        );
+    }
+
+    #[test]
+    fn test_count_fishes_pow_mod() {
+        let waters = FishyWaters::from("3,4,3,1,2");
+        let prime = 1_000_000_007u64;
+
+        assert_eq!(
+            count_fishes_pow(&waters, 256, Some(prime)),
+            Some(count_fishes(&waters, 256) as u128 % prime as u128)
+        );
+        assert_eq!(
+            count_fishes_pow(&waters, 900, Some(prime)),
+            Some(simulate(Species::lanternfish(), &waters, 900).unwrap() % prime as u128)
+        );
+
+        assert_eq!(count_fishes_pow(&waters, 1_000_000_000_000, None), None);
+        assert!(count_fishes_pow(&waters, 1_000_000_000_000, Some(prime)).unwrap() < prime as u128);
+        assert_eq!(count_fishes_pow(&waters, 80, Some(0)), None);
+    }
+
+    #[test]
+    fn test_history() {
+        let waters = FishyWaters::from("3,4,3,1,2");
+        let history = History::from(Population::new(Species::lanternfish(), &waters))
+            .take(81)
+            .collect::<Vec<DayStats>>();
+
+        assert_eq!(history[0].timers, vec![0, 1, 1, 2, 1, 0, 0, 0, 0]);
+        assert_eq!(history[0].growth, None);
+        assert_eq!(history[1].timers, vec![1, 1, 2, 1, 0, 0, 0, 0, 0]);
+        assert_eq!(history[2].timers, vec![1, 2, 1, 0, 0, 0, 1, 0, 1]);
+        assert_eq!(history[2].growth, Some(6.0 / 5.0));
+        assert_eq!(history[18].total, 26);
+        assert_eq!(history[80].total, 5934);
+
+        let history = History::from(Population::new(Species::lanternfish(), &waters))
+            .take(1100)
+            .collect::<Vec<DayStats>>();
+        let last = history.last().unwrap();
+
+        assert!(history.len() < 1100);
+        assert_eq!(
+            count_fishes_pow(&waters, last.day as u64, None),
+            Some(last.total)
+        );
+        assert_eq!(count_fishes_pow(&waters, last.day as u64 + 1, None), None);
+    }
+
+    #[test]
+    fn test_history_csv() {
+        let waters = FishyWaters::from("3,4,3,1,2");
+        let csv =
+            history_csv(History::from(Population::new(Species::lanternfish(), &waters)).take(3));
+
+        assert_eq!(
+            csv,
+            "day,timer_0,timer_1,timer_2,timer_3,timer_4,timer_5,timer_6,timer_7,timer_8,total,growth\n\
+             0,0,1,1,2,1,0,0,0,0,5,\n\
+             1,1,1,2,1,0,0,0,0,0,5,1.000000\n\
+             2,1,2,1,0,0,0,1,0,1,6,1.200000\n"
+        );
+    }
 }
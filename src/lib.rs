@@ -22,6 +22,15 @@ pub mod fold;
 pub mod poly;
 pub mod chiton;
 pub mod pathfinding;
+pub mod overlap;
+pub mod display;
+pub mod pairs;
+pub mod repair;
+pub mod report;
+pub mod nesting;
+
+#[cfg(test)]
+mod testing;
 
 pub mod day1;
 pub mod day2;
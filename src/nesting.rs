@@ -0,0 +1,192 @@
+use crate::bracket::{lint_line_recovering, Bracket, BracketShape, Diagnostic, Severity};
+use std::collections::HashMap;
+use std::ops::Range;
+
+// A bracket pair and everything nested in it. `close_pos` stays empty for
+// openers an incomplete line never got round to closing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub shape: BracketShape,
+    pub open_pos: usize,
+    pub close_pos: Option<usize>,
+    pub children: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tree {
+    pub roots: Vec<Node>,
+}
+
+fn visit<'a>(nodes: &'a [Node], depth: usize, f: &mut impl FnMut(&'a [Node], usize)) {
+    f(nodes, depth);
+
+    for node in nodes {
+        visit(&node.children, depth + 1, f);
+    }
+}
+
+impl TryFrom<&[Bracket]> for Tree {
+    type Error = Diagnostic;
+
+    // Corrupted lines have no tree; the first corrupt bracket is handed back instead
+    fn try_from(line: &[Bracket]) -> Result<Self, Self::Error> {
+        if let Some(error) = lint_line_recovering(line)
+            .into_iter()
+            .find(|d| d.severity == Severity::Error)
+        {
+            return Err(error);
+        }
+
+        let mut open: Vec<Node> = vec![];
+        let mut roots = vec![];
+
+        let attach = |node: Node, open: &mut Vec<Node>, roots: &mut Vec<Node>| match open.last_mut()
+        {
+            Some(parent) => parent.children.push(node),
+            None => roots.push(node),
+        };
+
+        for (i, bracket) in line.iter().enumerate() {
+            match bracket {
+                Bracket::Open(shape) => open.push(Node {
+                    shape: *shape,
+                    open_pos: i,
+                    close_pos: None,
+                    children: vec![],
+                }),
+                Bracket::Close(_) => {
+                    let mut node = open.pop().expect("Closer without opener after linting");
+
+                    node.close_pos = Some(i);
+                    attach(node, &mut open, &mut roots);
+                }
+            }
+        }
+
+        while let Some(node) = open.pop() {
+            attach(node, &mut open, &mut roots);
+        }
+
+        Ok(Tree { roots })
+    }
+}
+
+impl Tree {
+    pub fn is_complete(&self) -> bool {
+        let mut complete = true;
+
+        visit(&self.roots, 1, &mut |nodes, _| {
+            complete &= nodes.iter().all(|node| node.close_pos.is_some())
+        });
+
+        complete
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.depth_histogram().len()
+    }
+
+    // Number of pairs at each depth, outermost pairs at index 0
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        let mut histogram = vec![];
+
+        visit(&self.roots, 0, &mut |nodes, depth| {
+            if nodes.is_empty() {
+                return;
+            }
+
+            if histogram.len() <= depth {
+                histogram.resize(depth + 1, 0);
+            }
+
+            histogram[depth] += nodes.len();
+        });
+
+        histogram
+    }
+
+    pub fn shape_counts(&self) -> HashMap<BracketShape, usize> {
+        let mut counts = HashMap::new();
+
+        visit(&self.roots, 0, &mut |nodes, _| {
+            for node in nodes {
+                *counts.entry(node.shape).or_insert(0) += 1;
+            }
+        });
+
+        counts
+    }
+
+    // Siblings sit right next to each other, so every run of closed siblings is
+    // balanced; the longest of those is the longest balanced part of the line
+    pub fn longest_balanced(&self) -> Option<Range<usize>> {
+        let mut longest: Option<Range<usize>> = None;
+
+        visit(&self.roots, 0, &mut |nodes, _| {
+            let mut run: Option<Range<usize>> = None;
+
+            for node in nodes {
+                run = match (node.close_pos, run.take()) {
+                    (Some(close), Some(r)) => Some(r.start..close + 1),
+                    (Some(close), None) => Some(node.open_pos..close + 1),
+                    (None, _) => None,
+                };
+
+                if let Some(r) = &run {
+                    if longest.as_ref().is_none_or(|l| r.len() > l.len()) {
+                        longest = Some(r.clone());
+                    }
+                }
+            }
+        });
+
+        longest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bracket::parse_line;
+
+    #[test]
+    fn test_complete_tree() {
+        let line = parse_line("[<>({}){}[([])<>]]");
+        let tree = Tree::try_from(line.as_slice()).unwrap();
+
+        assert!(tree.is_complete());
+        assert_eq!(tree.roots.len(), 1);
+        assert_eq!(tree.roots[0].children.len(), 4);
+        assert_eq!(tree.max_depth(), 4);
+        assert_eq!(tree.depth_histogram(), vec![1, 4, 3, 1]);
+        assert_eq!(tree.shape_counts()[&BracketShape::Square], 3);
+        assert_eq!(tree.shape_counts()[&BracketShape::Curly], 2);
+        assert_eq!(tree.longest_balanced(), Some(0..18));
+    }
+
+    #[test]
+    fn test_partial_tree() {
+        let line = parse_line("[({(<(())[]>[[{[]{<()<>>");
+        let tree = Tree::try_from(line.as_slice()).unwrap();
+
+        assert!(!tree.is_complete());
+        assert_eq!(tree.max_depth(), 10);
+        assert_eq!(tree.roots[0].close_pos, None);
+        assert_eq!(tree.roots[0].children[0].shape, BracketShape::Round);
+        assert_eq!(tree.longest_balanced(), Some(4..12));
+
+        let line = parse_line("<>((");
+        let tree = Tree::try_from(line.as_slice()).unwrap();
+
+        assert_eq!(tree.depth_histogram(), vec![2, 1]);
+        assert_eq!(tree.longest_balanced(), Some(0..2));
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let line = parse_line("{([(<{}[<>[]}>{[]{[(<()>");
+
+        assert_eq!(Tree::try_from(line.as_slice()).unwrap_err().pos, 12);
+        assert_eq!(Tree::try_from(&[][..]).unwrap().longest_balanced(), None);
+    }
+}
@@ -1,4 +1,60 @@
-use std::collections::HashSet;
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Neighbourhood {
+    Four,
+    Eight,
+    // Rows of hexagons, every odd row shifted half a cell to the right
+    Hex,
+}
+
+impl Neighbourhood {
+    fn offsets(&self, y: usize) -> &'static [(i64, i64)] {
+        match self {
+            Neighbourhood::Four => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Neighbourhood::Eight => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+            Neighbourhood::Hex if y.is_multiple_of(2) => {
+                &[(-1, -1), (0, -1), (-1, 0), (1, 0), (-1, 1), (0, 1)]
+            }
+            Neighbourhood::Hex => &[(0, -1), (1, -1), (-1, 0), (1, 0), (0, 1), (1, 1)],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edges {
+    Bounded,
+    Toroidal,
+}
+
+// An octopus flashes once its energy goes above `threshold`, and ends the step at `reset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rules {
+    pub neighbourhood: Neighbourhood,
+    pub threshold: u8,
+    pub reset: u8,
+    pub energy_per_step: u8,
+    pub edges: Edges,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            neighbourhood: Neighbourhood::Eight,
+            threshold: 9,
+            reset: 0,
+            energy_per_step: 1,
+            edges: Edges::Bounded,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Map {
@@ -6,6 +62,7 @@ pub struct Map {
     pub width: usize,
     pub height: usize,
     pub flash_count: usize,
+    pub rules: Rules,
 }
 
 impl From<&str> for Map {
@@ -31,98 +88,152 @@ impl From<&str> for Map {
             width,
             height,
             flash_count: 0,
+            rules: Rules::default(),
         }
     }
 }
 
 impl Map {
-    pub fn tick(&self) -> Map {
-        // +1 Energy
-        let mut wip: Vec<Vec<u8>> = self
-            .octopi
+    // Wrapping an odd number of hex rows puts two even rows next to each other,
+    // and the shifted offsets would no longer meet up
+    pub fn with_rules(self, rules: Rules) -> Map {
+        assert!(
+            !(rules.neighbourhood == Neighbourhood::Hex
+                && rules.edges == Edges::Toroidal
+                && !self.height.is_multiple_of(2)),
+            "A toroidal hex grid needs an even number of rows"
+        );
+
+        Map { rules, ..self }
+    }
+
+    pub fn neighbours(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let (width, height) = (self.width as i64, self.height as i64);
+
+        let mut neighbours: Vec<(usize, usize)> = self
+            .rules
+            .neighbourhood
+            .offsets(y)
             .iter()
-            .map(|row| row.iter().map(|octopus| octopus + 1).collect())
+            .map(|(d_x, d_y)| (x as i64 + d_x, y as i64 + d_y))
+            .filter_map(|(n_x, n_y)| match self.rules.edges {
+                Edges::Bounded => {
+                    (n_x >= 0 && n_y >= 0 && n_x < width && n_y < height).then_some((n_x, n_y))
+                }
+                Edges::Toroidal => Some((n_x.rem_euclid(width), n_y.rem_euclid(height))),
+            })
+            .map(|(n_x, n_y)| (n_x as usize, n_y as usize))
+            .filter(|n| *n != (x, y))
             .collect();
 
-        let mut flashed: HashSet<(usize, usize)> = HashSet::new();
+        // Wrapping round a grid only a cell or two across reaches the same octopus twice
+        neighbours.sort_unstable();
+        neighbours.dedup();
 
-        let mut flash_one = || {
-            for y in 0..self.height {
-                for x in 0..self.width {
-                    let value = wip[y][x];
+        neighbours
+    }
 
-                    if value > 9 && !flashed.contains(&(x, y)) {
-                        if y > 0 && x > 0 && !flashed.contains(&(x - 1, y - 1)) {
-                            wip[y - 1][x - 1] += 1;
-                        }
-                        if y > 0 && !flashed.contains(&(x, y - 1)) {
-                            wip[y - 1][x] += 1;
-                        }
-                        if y > 0 && x < self.width - 1 && !flashed.contains(&(x + 1, y - 1)) {
-                            wip[y - 1][x + 1] += 1;
-                        }
+    // Same as `neighbours`, without building a list for every flash
+    fn each_neighbour(&self, x: usize, y: usize, mut f: impl FnMut(usize, usize)) {
+        if self.rules.edges == Edges::Toroidal && (self.width < 3 || self.height < 3) {
+            for (n_x, n_y) in self.neighbours(x, y) {
+                f(n_x, n_y);
+            }
 
-                        if x > 0 && !flashed.contains(&(x - 1, y)) {
-                            wip[y][x - 1] += 1;
-                        }
-                        if x < self.width - 1 && !flashed.contains(&(x + 1, y)) {
-                            wip[y][x + 1] += 1;
-                        }
+            return;
+        }
 
-                        if x > 0 && y < self.height - 1 && !flashed.contains(&(x - 1, y + 1)) {
-                            wip[y + 1][x - 1] += 1;
-                        }
-                        if y < self.height - 1 && !flashed.contains(&(x, y + 1)) {
-                            wip[y + 1][x] += 1;
-                        }
-                        if x < self.width - 1
-                            && y < self.height - 1
-                            && !flashed.contains(&(x + 1, y + 1))
-                        {
-                            wip[y + 1][x + 1] += 1;
-                        }
+        let (width, height) = (self.width as i64, self.height as i64);
 
-                        flashed.insert((x, y));
+        for (d_x, d_y) in self.rules.neighbourhood.offsets(y) {
+            let (mut n_x, mut n_y) = (x as i64 + d_x, y as i64 + d_y);
 
-                        wip[y][x] = 0;
+            match self.rules.edges {
+                Edges::Bounded if n_x < 0 || n_y < 0 || n_x >= width || n_y >= height => continue,
+                Edges::Bounded => {}
+                Edges::Toroidal => {
+                    n_x = n_x.rem_euclid(width);
+                    n_y = n_y.rem_euclid(height);
+                }
+            }
 
-                        return true;
-                    }
+            f(n_x as usize, n_y as usize);
+        }
+    }
+
+    // Advances the map in place and returns how many octopi flashed. Every octopus
+    // joins the worklist at most once, the moment it goes over the threshold, so a
+    // step is linear in the size of the grid.
+    pub fn step(&mut self) -> usize {
+        let rules = self.rules;
+        let mut octopi = std::mem::take(&mut self.octopi);
+        let mut flashed = vec![false; self.width * self.height];
+        let mut worklist = vec![];
+
+        for (y, row) in octopi.iter_mut().enumerate() {
+            for (x, octopus) in row.iter_mut().enumerate() {
+                *octopus = octopus.saturating_add(rules.energy_per_step);
+
+                if *octopus > rules.threshold {
+                    worklist.push((x, y));
                 }
             }
+        }
 
-            false
-        };
+        let mut flashes = 0;
 
-        let mut run = true;
+        while let Some((x, y)) = worklist.pop() {
+            flashed[y * self.width + x] = true;
+            flashes += 1;
 
-        while run {
-            run = flash_one();
+            self.each_neighbour(x, y, |n_x, n_y| {
+                let octopus = &mut octopi[n_y][n_x];
+
+                if flashed[n_y * self.width + n_x] || *octopus > rules.threshold {
+                    return;
+                }
+
+                *octopus = octopus.saturating_add(1);
+
+                if *octopus > rules.threshold {
+                    worklist.push((n_x, n_y));
+                }
+            });
         }
 
-        Map {
-            octopi: wip,
-            width: self.width,
-            height: self.height,
-            flash_count: self.flash_count + flashed.len(),
+        for (y, row) in octopi.iter_mut().enumerate() {
+            for (x, octopus) in row.iter_mut().enumerate() {
+                if flashed[y * self.width + x] {
+                    *octopus = rules.reset;
+                }
+            }
         }
+
+        self.octopi = octopi;
+        self.flash_count += flashes;
+
+        flashes
+    }
+
+    pub fn tick(&self) -> Map {
+        let mut next = self.clone();
+
+        next.step();
+        next
     }
 }
 
+// First step in which every octopus flashes. A map that starts out with every
+// octopus at the reset value is already in sync, at step 0.
 pub fn find_sync_flash_step(map: &Map) -> usize {
+    if map.octopi.iter().flatten().all(|o| *o == map.rules.reset) {
+        return 0;
+    }
+
     let mut step = map.clone();
-    let mut i = 0;
-
-    while step
-        .octopi
-        .iter()
-        .flatten()
-        .map(|o| *o as usize)
-        .sum::<usize>()
-        > 0
-    {
-        step = step.tick();
+    let mut i = 1;
 
+    while step.step() != map.width * map.height {
         i += 1;
     }
 
@@ -132,6 +243,7 @@ pub fn find_sync_flash_step(map: &Map) -> usize {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::Lcg;
 
     #[test]
     fn test_small() {
@@ -264,4 +376,195 @@ mod tests {
 
         assert_eq!(find_sync_flash_step(&map), 195);
     }
+
+    #[test]
+    fn test_rules() {
+        let map = Map::from(
+            "000\n\
+             090\n\
+             000",
+        );
+
+        let four = map.clone().with_rules(Rules {
+            neighbourhood: Neighbourhood::Four,
+            ..Rules::default()
+        });
+
+        assert_eq!(
+            four.tick().octopi,
+            vec![vec![1, 2, 1], vec![2, 0, 2], vec![1, 2, 1]]
+        );
+
+        let lazy = map.clone().with_rules(Rules {
+            threshold: 10,
+            reset: 3,
+            energy_per_step: 2,
+            ..Rules::default()
+        });
+
+        assert_eq!(
+            lazy.tick().octopi,
+            vec![vec![3, 3, 3], vec![3, 3, 3], vec![3, 3, 3]]
+        );
+        assert_eq!(lazy.tick().flash_count, 1);
+    }
+
+    #[test]
+    fn test_edges_and_hex() {
+        let map = Map::from("9000\n0000\n0000").with_rules(Rules {
+            edges: Edges::Toroidal,
+            ..Rules::default()
+        });
+
+        assert_eq!(
+            map.tick().octopi,
+            vec![vec![0, 2, 1, 2], vec![2, 2, 1, 2], vec![2, 2, 1, 2]]
+        );
+
+        let hex = Map::from("000\n090\n000").with_rules(Rules {
+            neighbourhood: Neighbourhood::Hex,
+            ..Rules::default()
+        });
+
+        assert_eq!(hex.neighbours(1, 1).len(), 6);
+        assert_eq!(
+            hex.tick().octopi,
+            vec![vec![1, 2, 2], vec![2, 0, 2], vec![1, 2, 2]]
+        );
+    }
+
+    #[test]
+    fn test_hex_torus_symmetric() {
+        let map = Map::from("0000\n0000\n0000\n0000\n0000\n0000").with_rules(Rules {
+            neighbourhood: Neighbourhood::Hex,
+            edges: Edges::Toroidal,
+            ..Rules::default()
+        });
+
+        for y in 0..map.height {
+            for x in 0..map.width {
+                assert_eq!(map.neighbours(x, y).len(), 6);
+
+                for (n_x, n_y) in map.neighbours(x, y) {
+                    assert!(map.neighbours(n_x, n_y).contains(&(x, y)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "even number of rows")]
+    fn test_hex_torus_odd_rows() {
+        Map::from("0000\n0000\n0000").with_rules(Rules {
+            neighbourhood: Neighbourhood::Hex,
+            edges: Edges::Toroidal,
+            ..Rules::default()
+        });
+    }
+
+    #[test]
+    fn test_sync_flash_with_rules() {
+        let map = Map::from("99\n99").with_rules(Rules {
+            reset: 5,
+            ..Rules::default()
+        });
+
+        assert_eq!(find_sync_flash_step(&map), 1);
+        assert_eq!(find_sync_flash_step(&Map::from("00\n00")), 0);
+    }
+
+    #[test]
+    fn test_large_grid() {
+        let mut lcg = Lcg::new(11);
+        let input = (0..300)
+            .map(|_| {
+                (0..300)
+                    .map(|_| lcg.below(10).to_string())
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let mut map = Map::from(input.as_str());
+        let mut flashes = 0;
+
+        for _ in 0..20 {
+            flashes += map.step();
+        }
+
+        assert_eq!(map.flash_count, flashes);
+        assert!(map.octopi.iter().flatten().all(|o| *o <= 9));
+    }
+
+    // The rescanning flash search `step` replaced, kept to check it against
+    fn rescanning_tick(map: &Map) -> Map {
+        let mut wip: Vec<Vec<u8>> = map
+            .octopi
+            .iter()
+            .map(|row| row.iter().map(|o| o + map.rules.energy_per_step).collect())
+            .collect();
+        let mut flashed = std::collections::HashSet::new();
+
+        'scan: loop {
+            for y in 0..map.height {
+                for x in 0..map.width {
+                    if wip[y][x] > map.rules.threshold && flashed.insert((x, y)) {
+                        for (n_x, n_y) in map.neighbours(x, y) {
+                            if !flashed.contains(&(n_x, n_y)) {
+                                wip[n_y][n_x] += 1;
+                            }
+                        }
+
+                        wip[y][x] = map.rules.reset;
+                        continue 'scan;
+                    }
+                }
+            }
+
+            break;
+        }
+
+        Map {
+            octopi: wip,
+            flash_count: map.flash_count + flashed.len(),
+            ..map.clone()
+        }
+    }
+
+    #[test]
+    fn test_against_rescanning() {
+        let mut lcg = Lcg::new(3);
+        let input = (0..10)
+            .map(|_| {
+                (0..12)
+                    .map(|_| lcg.below(10).to_string())
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        for neighbourhood in [
+            Neighbourhood::Four,
+            Neighbourhood::Eight,
+            Neighbourhood::Hex,
+        ] {
+            for edges in [Edges::Bounded, Edges::Toroidal] {
+                let mut map = Map::from(input.as_str()).with_rules(Rules {
+                    neighbourhood,
+                    edges,
+                    reset: 1,
+                    ..Rules::default()
+                });
+                let mut reference = map.clone();
+
+                for _ in 0..50 {
+                    map.step();
+                    reference = rescanning_tick(&reference);
+
+                    assert_eq!(map.octopi, reference.octopi);
+                    assert_eq!(map.flash_count, reference.flash_count);
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,337 @@
+use crate::vents::Segment;
+use std::collections::{HashMap, HashSet};
+
+type Point = (i64, i64);
+
+fn gcd(a: i64, b: i64) -> i64 {
+    match b {
+        0 => a.abs(),
+        _ => gcd(b, a % b),
+    }
+}
+
+// Smallest integer step along the segment, pointing "rightwards" so that
+// both ends of a segment agree on the direction of their line
+fn primitive(dx: i64, dy: i64) -> Point {
+    let g = gcd(dx, dy);
+    let (dx, dy) = (dx / g, dy / g);
+
+    if dx < 0 || (dx == 0 && dy < 0) {
+        (-dx, -dy)
+    } else {
+        (dx, dy)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Run {
+    from: i64,
+    to: i64,
+    count: u64,
+}
+
+// An infinite line through `base`, where lattice point `base + t * dir` sits at parameter t
+#[derive(Debug)]
+struct Line {
+    base: Point,
+    dir: Point,
+    intervals: Vec<(i64, i64)>,
+    runs: Vec<Run>,
+}
+
+impl Line {
+    fn param(&self, p: Point) -> Option<i64> {
+        let rel = (p.0 - self.base.0, p.1 - self.base.1);
+
+        if rel.0 * self.dir.1 != rel.1 * self.dir.0 {
+            return None;
+        }
+
+        Some((rel.0 * self.dir.0 + rel.1 * self.dir.1) / (self.dir.0.pow(2) + self.dir.1.pow(2)))
+    }
+
+    fn point(&self, t: i64) -> Point {
+        (self.base.0 + t * self.dir.0, self.base.1 + t * self.dir.1)
+    }
+
+    fn coverage_at(&self, t: i64) -> u64 {
+        let i = self.runs.partition_point(|run| run.to < t);
+
+        match self.runs.get(i) {
+            Some(run) if run.from <= t => run.count,
+            _ => 0,
+        }
+    }
+
+    fn sweep(&mut self) {
+        let mut events: Vec<(i64, i64)> = vec![];
+
+        for (from, to) in &self.intervals {
+            events.push((*from, 1));
+            events.push((*to + 1, -1));
+        }
+
+        events.sort_unstable();
+
+        let mut count = 0i64;
+        let mut i = 0;
+
+        while i < events.len() {
+            let t = events[i].0;
+
+            while i < events.len() && events[i].0 == t {
+                count += events[i].1;
+                i += 1;
+            }
+
+            if count > 0 {
+                if let Some(next) = events.get(i) {
+                    self.runs.push(Run {
+                        from: t,
+                        to: next.0 - 1,
+                        count: count as u64,
+                    });
+                }
+            }
+        }
+    }
+
+    // Lattice point where two non-parallel lines cross, if there is one
+    fn cross(&self, other: &Line) -> Option<(i64, i64)> {
+        let det =
+            self.dir.0 as i128 * other.dir.1 as i128 - self.dir.1 as i128 * other.dir.0 as i128;
+
+        if det == 0 {
+            return None;
+        }
+
+        let rel = (
+            (other.base.0 - self.base.0) as i128,
+            (other.base.1 - self.base.1) as i128,
+        );
+        let s = rel.0 * other.dir.1 as i128 - rel.1 * other.dir.0 as i128;
+        let u = rel.0 * self.dir.1 as i128 - rel.1 * self.dir.0 as i128;
+
+        if s % det != 0 || u % det != 0 {
+            return None;
+        }
+
+        Some(((s / det) as i64, (u / det) as i64))
+    }
+}
+
+fn build_lines(segments: &[Segment]) -> (Vec<Line>, HashMap<Point, u64>) {
+    let mut lookup: HashMap<(Point, i64), usize> = HashMap::new();
+    let mut lines: Vec<Line> = vec![];
+    let mut dots: HashMap<Point, u64> = HashMap::new();
+
+    for segment in segments {
+        let from = (segment.from.0 as i64, segment.from.1 as i64);
+        let to = (segment.to.0 as i64, segment.to.1 as i64);
+
+        if from == to {
+            *dots.entry(from).or_insert(0) += 1;
+            continue;
+        }
+
+        let dir = primitive(to.0 - from.0, to.1 - from.1);
+        let offset = dir.1 * from.0 - dir.0 * from.1;
+
+        let line_i = *lookup.entry((dir, offset)).or_insert_with(|| {
+            lines.push(Line {
+                base: from,
+                dir,
+                intervals: vec![],
+                runs: vec![],
+            });
+
+            lines.len() - 1
+        });
+
+        let line = &mut lines[line_i];
+        let (a, b) = (
+            line.param(from).expect("Segment off its own line"),
+            line.param(to).expect("Segment off its own line"),
+        );
+
+        line.intervals.push((a.min(b), a.max(b)));
+    }
+
+    for line in lines.iter_mut() {
+        line.sweep();
+    }
+
+    (lines, dots)
+}
+
+// Counts the lattice points covered by at least `threshold` segments, without
+// visiting every covered point. Collinear segments are merged into runs on their
+// shared line, and only the points where distinct lines cross are looked at one
+// by one.
+pub fn count_covered(segments: &[Segment], threshold: u64) -> usize {
+    let threshold = threshold.max(1);
+    let (lines, dots) = build_lines(segments);
+
+    let mut crossings: HashMap<Point, HashSet<usize>> = HashMap::new();
+
+    for i in 0..lines.len() {
+        for j in (i + 1)..lines.len() {
+            if let Some((s, u)) = lines[i].cross(&lines[j]) {
+                if lines[i].coverage_at(s) > 0 && lines[j].coverage_at(u) > 0 {
+                    let through = crossings.entry(lines[i].point(s)).or_default();
+
+                    through.insert(i);
+                    through.insert(j);
+                }
+            }
+        }
+    }
+
+    for dot in dots.keys() {
+        let through = crossings.entry(*dot).or_default();
+
+        for (i, line) in lines.iter().enumerate() {
+            if let Some(t) = line.param(*dot) {
+                if line.coverage_at(t) > 0 {
+                    through.insert(i);
+                }
+            }
+        }
+    }
+
+    let mut count: i64 = lines
+        .iter()
+        .flat_map(|line| line.runs.iter())
+        .filter(|run| run.count >= threshold)
+        .map(|run| run.to - run.from + 1)
+        .sum();
+
+    for (point, through) in &crossings {
+        let mut total = *dots.get(point).unwrap_or(&0);
+
+        for i in through {
+            let line = &lines[*i];
+            let coverage = line.coverage_at(line.param(*point).expect("Crossing off its line"));
+
+            // Already tallied once as part of this line's runs
+            if coverage >= threshold {
+                count -= 1;
+            }
+
+            total += coverage;
+        }
+
+        if total >= threshold {
+            count += 1;
+        }
+    }
+
+    count as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Lcg;
+    use crate::vents::{Map, SegmentKind};
+
+    fn segments(input: &str) -> Vec<Segment> {
+        input
+            .lines()
+            .map(|line| Segment::try_from(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_sample() {
+        let map = Map::try_from(
+            "0,9 -> 5,9\n\
+                                         8,0 -> 0,8\n\
+                                         9,4 -> 3,4\n\
+                                         2,2 -> 2,1\n\
+                                         7,0 -> 7,4\n\
+                                         6,4 -> 2,0\n\
+                                         0,9 -> 2,9\n\
+                                         3,4 -> 1,4\n\
+                                         0,0 -> 8,8\n\
+                                         5,5 -> 8,2",
+        )
+        .unwrap();
+
+        assert_eq!(count_covered(&map.segments, 2), 12);
+        assert_eq!(
+            count_covered(&map.segments, 2),
+            map.coverage(&SegmentKind::ALL)
+                .values()
+                .filter(|v| **v >= 2)
+                .count()
+        );
+    }
+
+    #[test]
+    fn test_puzzle_input() {
+        let map = Map::try_from(include_str!("../input/2021/day5.txt")).unwrap();
+        let coverage = map.coverage(&SegmentKind::ALL);
+
+        for threshold in 1..5 {
+            assert_eq!(
+                count_covered(&map.segments, threshold),
+                coverage.values().filter(|v| **v >= threshold).count()
+            );
+        }
+    }
+
+    #[test]
+    fn test_collinear() {
+        let segs = segments("0,0 -> 6,0\n9,0 -> 2,0\n4,0 -> 5,0");
+
+        assert_eq!(count_covered(&segs, 1), 10);
+        assert_eq!(count_covered(&segs, 2), 5);
+        assert_eq!(count_covered(&segs, 3), 2);
+
+        let segs = segments("0,0 -> 4,2\n2,1 -> 8,4");
+
+        assert_eq!(count_covered(&segs, 2), 2);
+    }
+
+    #[test]
+    fn test_crossings() {
+        assert_eq!(count_covered(&segments("0,0 -> 4,2\n0,2 -> 4,0"), 2), 1);
+        assert_eq!(count_covered(&segments("0,0 -> 1,1\n0,1 -> 1,0"), 2), 0);
+        assert_eq!(
+            count_covered(&segments("0,0 -> 2,2\n2,0 -> 0,2\n1,1 -> 1,1"), 3),
+            1
+        );
+        assert_eq!(
+            count_covered(
+                &segments(
+                    "0,0 -> 3000000,3000000\n3000000,0 -> 0,3000000\n0,1500000 -> 3000000,1500000"
+                ),
+                3
+            ),
+            1
+        );
+    }
+
+    #[test]
+    fn test_against_raster() {
+        let mut lcg = Lcg::new(7);
+        let mut next = || lcg.below(16);
+
+        let input = (0..40)
+            .map(|_| format!("{},{} -> {},{}", next(), next(), next(), next()))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let map = Map::try_from(input.as_str()).unwrap();
+
+        for threshold in 1..5 {
+            assert_eq!(
+                count_covered(&map.segments, threshold),
+                map.coverage(&SegmentKind::ALL)
+                    .values()
+                    .filter(|v| **v >= threshold)
+                    .count()
+            );
+        }
+    }
+}
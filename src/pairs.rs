@@ -0,0 +1,258 @@
+use crate::bracket::{Bracket, BracketShape};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Pair {
+    open: String,
+    close: String,
+    shape: BracketShape,
+}
+
+// Stretch of text, like a string literal or a comment, whose brackets don't count
+#[derive(Debug, Clone, PartialEq)]
+struct Region {
+    open: String,
+    close: String,
+    escape: Option<char>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Token {
+    pub bracket: Bracket,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PairTable {
+    pairs: Vec<Pair>,
+    regions: Vec<Region>,
+}
+
+fn is_word(s: &str) -> bool {
+    s.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+impl PairTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn standard() -> Self {
+        Self::new()
+            .shaped("(", ")", BracketShape::Round)
+            .shaped("[", "]", BracketShape::Square)
+            .shaped("{", "}", BracketShape::Curly)
+            .shaped("<", ">", BracketShape::Angular)
+    }
+
+    // Angle brackets are left out, in C they are far more often comparisons
+    pub fn c_like() -> Self {
+        Self::new()
+            .shaped("(", ")", BracketShape::Round)
+            .shaped("[", "]", BracketShape::Square)
+            .shaped("{", "}", BracketShape::Curly)
+            .region("\"", "\"", Some('\\'))
+            .region("'", "'", Some('\\'))
+            .region("//", "\n", None)
+            .region("/*", "*/", None)
+    }
+
+    fn shaped(mut self, open: &str, close: &str, shape: BracketShape) -> Self {
+        self.pairs.push(Pair {
+            open: open.to_string(),
+            close: close.to_string(),
+            shape,
+        });
+
+        self
+    }
+
+    // Adds a pair with its own `BracketShape::Custom`. Pairs made of letters, like
+    // `begin`/`end`, only match as whole words.
+    pub fn pair(self, open: &str, close: &str) -> Self {
+        let n = self
+            .pairs
+            .iter()
+            .filter(|pair| matches!(pair.shape, BracketShape::Custom(_)))
+            .count();
+
+        self.shaped(open, close, BracketShape::Custom(n))
+    }
+
+    // An unterminated region runs to the end of the text
+    pub fn region(mut self, open: &str, close: &str, escape: Option<char>) -> Self {
+        self.regions.push(Region {
+            open: open.to_string(),
+            close: close.to_string(),
+            escape,
+        });
+
+        self
+    }
+
+    pub fn symbol(&self, bracket: &Bracket) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|pair| pair.shape == bracket.shape())
+            .map(|pair| match bracket {
+                Bracket::Open(_) => pair.open.as_str(),
+                Bracket::Close(_) => pair.close.as_str(),
+            })
+    }
+
+    // The table's spelling of `bracket`. Built-in shapes the table leaves out
+    // keep their usual characters.
+    pub fn render(&self, bracket: &Bracket) -> String {
+        self.symbol(bracket)
+            .map_or_else(|| bracket.to_string(), str::to_string)
+    }
+
+    // Longest delimiter starting at `offset`
+    fn bracket_at(&self, text: &str, offset: usize) -> Option<(usize, Bracket)> {
+        let rest = &text[offset..];
+        let before = text[..offset].chars().next_back();
+        let word_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+
+        self.pairs
+            .iter()
+            .flat_map(|pair| {
+                [
+                    (pair.open.as_str(), Bracket::Open(pair.shape)),
+                    (pair.close.as_str(), Bracket::Close(pair.shape)),
+                ]
+            })
+            .filter(|(s, _)| !s.is_empty() && rest.starts_with(s))
+            .filter(|(s, _)| {
+                !is_word(s) || !(word_char(before) || word_char(rest[s.len()..].chars().next()))
+            })
+            .max_by_key(|(s, _)| s.len())
+            .map(|(s, bracket)| (s.len(), bracket))
+    }
+
+    // Every bracket outside of the table's regions, with its 1-based line and column
+    pub fn tokenise(&self, text: &str) -> Vec<Token> {
+        let mut tokens = vec![];
+        let (mut offset, mut line, mut column) = (0, 1, 1);
+        let mut region: Option<&Region> = None;
+
+        while let Some(c) = text[offset..].chars().next() {
+            let rest = &text[offset..];
+            let mut step = c.len_utf8();
+
+            match region {
+                Some(r) => {
+                    if r.escape == Some(c) {
+                        step += rest[step..].chars().next().map_or(0, char::len_utf8);
+                    } else if rest.starts_with(&r.close) {
+                        step = r.close.len();
+                        region = None;
+                    }
+                }
+                None => {
+                    if let Some(r) = self
+                        .regions
+                        .iter()
+                        .find(|r| !r.open.is_empty() && rest.starts_with(&r.open))
+                    {
+                        step = r.open.len();
+                        region = Some(r);
+                    } else if let Some((len, bracket)) = self.bracket_at(text, offset) {
+                        tokens.push(Token {
+                            bracket,
+                            offset,
+                            line,
+                            column,
+                        });
+                        step = len;
+                    }
+                }
+            }
+
+            for c in text[offset..offset + step].chars() {
+                if c == '\n' {
+                    line += 1;
+                    column = 1;
+                } else {
+                    column += 1;
+                }
+            }
+
+            offset += step;
+        }
+
+        tokens
+    }
+
+    pub fn parse_line(&self, line: &str) -> Vec<Bracket> {
+        self.tokenise(line)
+            .iter()
+            .map(|token| token.bracket)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bracket::{lint_line, lint_line_recovering, parse_line};
+    use either::Either;
+
+    #[test]
+    fn test_standard() {
+        let table = PairTable::standard();
+
+        assert_eq!(
+            table.parse_line("{([(<{}[<>[]}>{[]{[(<()>"),
+            parse_line("{([(<{}[<>[]}>{[]{[(<()>")
+        );
+        assert_eq!(table.parse_line("f(x) = [1, 2]"), parse_line("()[]"));
+        assert_eq!(
+            table.symbol(&Bracket::Close(BracketShape::Curly)),
+            Some("}")
+        );
+    }
+
+    #[test]
+    fn test_custom_pairs() {
+        let table = PairTable::new().pair("«", "»").pair("begin", "end");
+        let brackets = table.parse_line("begin «quote» beginning; end");
+
+        assert_eq!(
+            brackets,
+            vec![
+                Bracket::Open(BracketShape::Custom(1)),
+                Bracket::Open(BracketShape::Custom(0)),
+                Bracket::Close(BracketShape::Custom(0)),
+                Bracket::Close(BracketShape::Custom(1)),
+            ]
+        );
+        assert_eq!(lint_line(&brackets), Either::Left(vec![]));
+        assert_eq!(table.symbol(&brackets[0]), Some("begin"));
+        assert_eq!(table.render(&brackets[1]), "«");
+
+        let diagnostics = lint_line_recovering(&table.parse_line("begin «"));
+
+        assert_eq!(diagnostics[0].message(&table), "« is never closed");
+        assert_eq!(diagnostics[1].suggestion(&table), "insert end");
+    }
+
+    #[test]
+    fn test_regions() {
+        let table = PairTable::c_like();
+        let source = "int main() {\n    \
+                          printf(\"(%c\\\")\", ']'); // }\n    \
+                          /* { */ return a < b;\n\
+                      }";
+        let tokens = table.tokenise(source);
+
+        assert_eq!(
+            tokens.iter().map(|t| t.bracket).collect::<Vec<Bracket>>(),
+            parse_line("(){()}")
+        );
+        assert_eq!((tokens[2].line, tokens[2].column), (1, 12));
+        assert_eq!((tokens[3].line, tokens[3].column), (2, 11));
+        assert_eq!((tokens[4].line, tokens[4].column), (2, 25));
+        assert_eq!((tokens[5].line, tokens[5].column), (4, 1));
+    }
+}
@@ -0,0 +1,309 @@
+use crate::bracket::{to_complete_score, to_error_score, Bracket, BracketShape};
+
+pub trait EditCosts {
+    fn insert(&self, bracket: &Bracket) -> usize;
+    fn delete(&self, bracket: &Bracket) -> usize;
+    fn substitute(&self, from: &Bracket, to: &Bracket) -> usize;
+}
+
+pub struct UnitCosts;
+
+impl EditCosts for UnitCosts {
+    fn insert(&self, _: &Bracket) -> usize {
+        1
+    }
+
+    fn delete(&self, _: &Bracket) -> usize {
+        1
+    }
+
+    fn substitute(&self, _: &Bracket, _: &Bracket) -> usize {
+        1
+    }
+}
+
+// Adding a bracket costs what it scores when completing a line, and getting rid
+// of a corrupt one costs its syntax error score
+pub struct PuzzleCosts;
+
+impl EditCosts for PuzzleCosts {
+    fn insert(&self, bracket: &Bracket) -> usize {
+        to_complete_score(&bracket.shape())
+    }
+
+    fn delete(&self, bracket: &Bracket) -> usize {
+        to_error_score(&bracket.shape())
+    }
+
+    fn substitute(&self, from: &Bracket, _: &Bracket) -> usize {
+        to_error_score(&from.shape())
+    }
+}
+
+// Positions are indices into the original line, insertions go in front of `pos`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edit {
+    Insert { pos: usize, bracket: Bracket },
+    Delete { pos: usize },
+    Substitute { pos: usize, bracket: Bracket },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Repair {
+    pub edits: Vec<Edit>,
+    pub line: Vec<Bracket>,
+    pub cost: usize,
+}
+
+// How the first bracket of a stretch of the line gets balanced
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Empty,
+    Delete,
+    Paired(BracketShape, usize),
+    InsertClose(BracketShape, usize),
+    InsertOpen(BracketShape),
+}
+
+// Keeps the bracket at `pos` as `bracket`, substituting it if need be
+fn place(
+    line: &[Bracket],
+    pos: usize,
+    bracket: Bracket,
+    edits: &mut Vec<Edit>,
+    out: &mut Vec<Bracket>,
+) {
+    if line[pos] != bracket {
+        edits.push(Edit::Substitute { pos, bracket });
+    }
+
+    out.push(bracket);
+}
+
+struct Table<'a> {
+    line: &'a [Bracket],
+    cost: Vec<Vec<usize>>,
+    step: Vec<Vec<Step>>,
+}
+
+impl Table<'_> {
+    fn walk(&self, i: usize, j: usize, edits: &mut Vec<Edit>, out: &mut Vec<Bracket>) {
+        match self.step[i][j] {
+            Step::Empty => {}
+            Step::Delete => {
+                edits.push(Edit::Delete { pos: i });
+                self.walk(i + 1, j, edits, out);
+            }
+            Step::Paired(shape, k) => {
+                place(self.line, i, Bracket::Open(shape), edits, out);
+                self.walk(i + 1, k, edits, out);
+                place(self.line, k, shape.close(), edits, out);
+                self.walk(k + 1, j, edits, out);
+            }
+            Step::InsertClose(shape, k) => {
+                place(self.line, i, Bracket::Open(shape), edits, out);
+                self.walk(i + 1, k, edits, out);
+                edits.push(Edit::Insert {
+                    pos: k,
+                    bracket: shape.close(),
+                });
+                out.push(shape.close());
+                self.walk(k, j, edits, out);
+            }
+            Step::InsertOpen(shape) => {
+                edits.push(Edit::Insert {
+                    pos: i,
+                    bracket: Bracket::Open(shape),
+                });
+                out.push(Bracket::Open(shape));
+                place(self.line, i, shape.close(), edits, out);
+                self.walk(i + 1, j, edits, out);
+            }
+        }
+    }
+}
+
+// Cheapest set of edits that balances the whole line, corrupted or not. Interval
+// dynamic programming over every stretch of the line: its first bracket is either
+// deleted, or (possibly substituted) paired with a later bracket, an inserted
+// closer or an inserted opener in front of it.
+pub fn repair(line: &[Bracket], costs: &impl EditCosts) -> Repair {
+    let n = line.len();
+    let mut shapes = vec![
+        BracketShape::Round,
+        BracketShape::Square,
+        BracketShape::Curly,
+        BracketShape::Angular,
+    ];
+
+    for bracket in line {
+        if !shapes.contains(&bracket.shape()) {
+            shapes.push(bracket.shape());
+        }
+    }
+
+    let substitute = |pos: usize, to: Bracket| {
+        if line[pos] == to {
+            0
+        } else {
+            costs.substitute(&line[pos], &to)
+        }
+    };
+
+    let mut table = Table {
+        line,
+        cost: vec![vec![0; n + 1]; n + 1],
+        step: vec![vec![Step::Empty; n + 1]; n + 1],
+    };
+
+    for len in 1..=n {
+        for i in 0..=(n - len) {
+            let j = i + len;
+            let cost = &table.cost;
+            let mut best: Option<(usize, Step)> = None;
+            let mut consider = |total: usize, step: Step| {
+                if best.is_none_or(|(cost, _)| total < cost) {
+                    best = Some((total, step));
+                }
+            };
+
+            // Closing at the very end wins ties, so that plain incomplete lines get completed
+            // rather than rearranged
+            for shape in &shapes {
+                let open = substitute(i, Bracket::Open(*shape));
+
+                for k in ((i + 1)..=j).rev() {
+                    consider(
+                        open + costs.insert(&shape.close()) + cost[i + 1][k] + cost[k][j],
+                        Step::InsertClose(*shape, k),
+                    );
+                }
+            }
+
+            for shape in &shapes {
+                let open = substitute(i, Bracket::Open(*shape));
+
+                for k in (i + 1)..j {
+                    consider(
+                        open + substitute(k, shape.close()) + cost[i + 1][k] + cost[k + 1][j],
+                        Step::Paired(*shape, k),
+                    );
+                }
+            }
+
+            consider(costs.delete(&line[i]) + cost[i + 1][j], Step::Delete);
+
+            for shape in &shapes {
+                consider(
+                    costs.insert(&Bracket::Open(*shape))
+                        + substitute(i, shape.close())
+                        + cost[i + 1][j],
+                    Step::InsertOpen(*shape),
+                );
+            }
+
+            let (total, step) = best.expect("Stretch with nothing to try");
+
+            table.cost[i][j] = total;
+            table.step[i][j] = step;
+        }
+    }
+
+    let mut edits = vec![];
+    let mut repaired = vec![];
+
+    table.walk(0, n, &mut edits, &mut repaired);
+
+    Repair {
+        edits,
+        line: repaired,
+        cost: table.cost[0][n],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bracket::{complete_line, lint_line_recovering, parse_line, parse_lines};
+
+    #[test]
+    fn test_repair_small() {
+        let repaired = repair(&parse_line("(]"), &UnitCosts);
+
+        assert_eq!(repaired.cost, 1);
+        assert_eq!(repaired.line, parse_line("()"));
+        assert_eq!(
+            repaired.edits,
+            vec![Edit::Substitute {
+                pos: 1,
+                bracket: Bracket::Close(BracketShape::Round)
+            }]
+        );
+
+        let repaired = repair(&parse_line("())"), &UnitCosts);
+
+        assert_eq!(repaired.cost, 1);
+        assert_eq!(repaired.line, parse_line("()"));
+        assert_eq!(repaired.edits, vec![Edit::Delete { pos: 2 }]);
+
+        let repaired = repair(&parse_line("(]"), &PuzzleCosts);
+
+        assert_eq!(repaired.cost, 3);
+        assert_eq!(repaired.line, parse_line("([])"));
+
+        assert_eq!(repair(&[], &UnitCosts).cost, 0);
+    }
+
+    // Makes touching existing brackets so dear that only completion is worth it
+    struct AppendOnly;
+
+    impl EditCosts for AppendOnly {
+        fn insert(&self, _: &Bracket) -> usize {
+            1
+        }
+
+        fn delete(&self, _: &Bracket) -> usize {
+            1000
+        }
+
+        fn substitute(&self, _: &Bracket, _: &Bracket) -> usize {
+            1000
+        }
+    }
+
+    #[test]
+    fn test_repair_sample() {
+        let lines = parse_lines(
+            "[({(<(())[]>[[{[]{<()<>>\n\
+                                                [(()[<>])]({[<{<<[]>>(\n\
+                                                {([(<{}[<>[]}>{[]{[(<()>\n\
+                                                (((({<>}<{<{<>}{[]{[]{}\n\
+                                                [[<[([]))<([[{}[[()]]]\n\
+                                                [{[{({}]{}}([{[{{{}}([]\n\
+                                                {<[[]]>}<{[{[{[]{()[[[]\n\
+                                                [<(<(<(<{}))><([]([]()\n\
+                                                <{([([[(<>()){}]>(<<{{\n\
+                                                <{([{{}}[<[[[<>{}]]]>[]]",
+        );
+
+        for line in &lines {
+            let repaired = repair(line, &UnitCosts);
+
+            assert_eq!(lint_line_recovering(&repaired.line), vec![]);
+            assert!(repaired.cost > 0);
+
+            if let Some(completed) = complete_line(line) {
+                assert!(repaired.cost <= completed.len() - line.len());
+
+                let repaired = repair(line, &AppendOnly);
+
+                assert_eq!(repaired.line, completed);
+                assert_eq!(repaired.cost, completed.len() - line.len());
+                assert!(repaired
+                    .edits
+                    .iter()
+                    .all(|edit| matches!(edit, Edit::Insert { pos, .. } if *pos == line.len())));
+            }
+        }
+    }
+}
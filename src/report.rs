@@ -0,0 +1,562 @@
+use crate::bracket::{
+    lint_line_recovering, to_error_score, Bracket, Diagnostic, Fix, Problem, Severity,
+};
+use crate::pairs::{PairTable, Token};
+use std::io::{Result, Write};
+
+// 1-based, columns counted in characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    fn of(token: &Token) -> Self {
+        Location {
+            line: token.line,
+            column: token.column,
+        }
+    }
+}
+
+// A diagnostic placed in the source text. `at` to `end` covers the offending
+// bracket, or the opener that was left open.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub diagnostic: Diagnostic,
+    pub message: String,
+    pub fix: String,
+    pub at: Location,
+    pub end: Location,
+    pub opened: Option<Location>,
+}
+
+impl Finding {
+    pub fn rule(&self) -> &'static str {
+        match self.diagnostic.problem {
+            Problem::Mismatched { .. } => "mismatched-closer",
+            Problem::Unopened { .. } => "unopened-closer",
+            Problem::Unclosed { .. } => "unclosed-opener",
+        }
+    }
+
+    fn severity(&self) -> Severity {
+        self.diagnostic.severity
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub source: String,
+    pub findings: Vec<Finding>,
+    // Each unit scores its first mismatched or unopened closer. Unlike
+    // `calc_syntax_score`, a closer with nothing open counts.
+    pub syntax_score: usize,
+}
+
+// Lints one run of brackets, adding its findings and returning its syntax score
+fn lint_tokens(tokens: &[Token], table: &PairTable, findings: &mut Vec<Finding>) -> usize {
+    let brackets: Vec<Bracket> = tokens.iter().map(|token| token.bracket).collect();
+    let width = |token: &Token| table.render(&token.bracket).chars().count();
+    let end_of = |token: &Token| Location {
+        line: token.line,
+        column: token.column + width(token) - 1,
+    };
+    // Past the last bracket is where missing closers go
+    let location = |pos: usize| match tokens.get(pos) {
+        Some(token) => Location::of(token),
+        None => tokens
+            .last()
+            .map_or(Location { line: 1, column: 1 }, |token| Location {
+                line: token.line,
+                column: token.column + width(token),
+            }),
+    };
+
+    let diagnostics = lint_line_recovering(&brackets);
+    let syntax_score = diagnostics
+        .iter()
+        .find_map(|diagnostic| match diagnostic.problem {
+            Problem::Mismatched { found, .. } | Problem::Unopened { found } => {
+                Some(to_error_score(&found))
+            }
+            Problem::Unclosed { .. } => None,
+        })
+        .unwrap_or(0);
+
+    for diagnostic in diagnostics {
+        let (anchor, opened) = match diagnostic.problem {
+            Problem::Mismatched { open_pos, .. } => (diagnostic.pos, Some(location(open_pos))),
+            Problem::Unopened { .. } => (diagnostic.pos, None),
+            Problem::Unclosed { open_pos, .. } => (open_pos, None),
+        };
+        let fix_at = match diagnostic.fix {
+            Fix::Insert { pos, .. } | Fix::Delete { pos } => location(pos),
+        };
+
+        findings.push(Finding {
+            message: diagnostic.message(table),
+            fix: format!(
+                "{} at {}:{}",
+                diagnostic.suggestion(table),
+                fix_at.line,
+                fix_at.column
+            ),
+            at: location(anchor),
+            end: end_of(&tokens[anchor]),
+            opened,
+            diagnostic,
+        });
+    }
+
+    syntax_score
+}
+
+impl Report {
+    // The whole text as one run of brackets, the way a source file nests
+    pub fn lint_source(text: &str, table: &PairTable) -> Report {
+        let mut findings = vec![];
+        let syntax_score = lint_tokens(&table.tokenise(text), table, &mut findings);
+
+        Report {
+            source: text.to_string(),
+            findings,
+            syntax_score,
+        }
+    }
+
+    // Every line on its own, the way the puzzle input is linted
+    pub fn lint_lines(text: &str, table: &PairTable) -> Report {
+        let mut findings = vec![];
+        let mut syntax_score = 0;
+        let mut offset = 0;
+
+        for (i, line) in text.split_inclusive('\n').enumerate() {
+            let tokens: Vec<Token> = table
+                .tokenise(line.trim_end_matches(['\n', '\r']))
+                .into_iter()
+                .map(|token| Token {
+                    line: i + 1,
+                    offset: token.offset + offset,
+                    ..token
+                })
+                .collect();
+
+            syntax_score += lint_tokens(&tokens, table, &mut findings);
+            offset += line.len();
+        }
+
+        Report {
+            source: text.to_string(),
+            findings,
+            syntax_score,
+        }
+    }
+
+    // Brackets that never were text, one line each, spelled the way `table`
+    // writes them
+    pub fn lint_brackets(lines: &[Vec<Bracket>], table: &PairTable) -> Report {
+        let mut findings = vec![];
+        let mut syntax_score = 0;
+        let mut source = vec![];
+        let mut offset = 0;
+
+        for (i, line) in lines.iter().enumerate() {
+            let mut text = String::new();
+            let tokens: Vec<Token> = line
+                .iter()
+                .map(|bracket| {
+                    let token = Token {
+                        bracket: *bracket,
+                        offset: offset + text.len(),
+                        line: i + 1,
+                        column: text.chars().count() + 1,
+                    };
+
+                    text.push_str(&table.render(bracket));
+                    token
+                })
+                .collect();
+
+            syntax_score += lint_tokens(&tokens, table, &mut findings);
+            offset += text.len() + 1;
+            source.push(text);
+        }
+
+        Report {
+            source: source.join("\n"),
+            findings,
+            syntax_score,
+        }
+    }
+
+    fn summary(&self) -> String {
+        let count = |severity: Severity| {
+            self.findings
+                .iter()
+                .filter(|finding| finding.severity() == severity)
+                .count()
+        };
+
+        format!(
+            "{} errors, {} warnings, syntax score {}",
+            count(Severity::Error),
+            count(Severity::Warning),
+            self.syntax_score
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportFormat {
+    Terminal,
+    Plain,
+    Json,
+    // Findings are reported against `uri`
+    Sarif { uri: String },
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+// Note for openers that can't be underlined along with their closer
+fn opened_elsewhere(finding: &Finding) -> String {
+    match finding.opened {
+        Some(opened) if opened.line != finding.at.line => {
+            format!(", opened at {}:{}", opened.line, opened.column)
+        }
+        _ => String::new(),
+    }
+}
+
+// Each source line with findings, followed by a coloured underline from the
+// opener to the offending bracket for every one of them
+pub fn write_terminal(out: &mut impl Write, report: &Report) -> Result<()> {
+    let lines: Vec<&str> = report.source.lines().collect();
+    let mut findings: Vec<&Finding> = report.findings.iter().collect();
+    let mut shown = None;
+
+    findings.sort_by_key(|finding| finding.at.line);
+
+    for finding in findings {
+        if shown != Some(finding.at.line) {
+            writeln!(
+                out,
+                "{:>4} | {}",
+                finding.at.line,
+                lines.get(finding.at.line - 1).unwrap_or(&"")
+            )?;
+            shown = Some(finding.at.line);
+        }
+
+        let start = match finding.opened {
+            Some(opened) if opened.line == finding.at.line => opened.column,
+            _ => finding.at.column,
+        };
+        let underline = match finding.end.column - start {
+            0 => "^".to_string(),
+            n => format!("^{}^", "-".repeat(n - 1)),
+        };
+        let colour = match finding.severity() {
+            Severity::Error => "31",
+            Severity::Warning => "33",
+        };
+
+        writeln!(
+            out,
+            "     | {}\x1b[1;{}m{} {}:\x1b[0m {}{} \x1b[2m({})\x1b[0m",
+            " ".repeat(start - 1),
+            colour,
+            underline,
+            finding.severity(),
+            finding.message,
+            opened_elsewhere(finding),
+            finding.fix
+        )?;
+    }
+
+    writeln!(out, "{}", report.summary())
+}
+
+pub fn write_plain(out: &mut impl Write, report: &Report) -> Result<()> {
+    for finding in &report.findings {
+        writeln!(
+            out,
+            "{}:{}: {}: {}{} ({})",
+            finding.at.line,
+            finding.at.column,
+            finding.severity(),
+            finding.message,
+            opened_elsewhere(finding),
+            finding.fix
+        )?;
+    }
+
+    writeln!(out, "{}", report.summary())
+}
+
+pub fn write_json(out: &mut impl Write, report: &Report) -> Result<()> {
+    let findings = report
+        .findings
+        .iter()
+        .map(|finding| {
+            let opened = match finding.opened {
+                Some(opened) => {
+                    format!("{{\"line\":{},\"column\":{}}}", opened.line, opened.column)
+                }
+                None => "null".to_string(),
+            };
+
+            format!(
+                "{{\"line\":{},\"column\":{},\"end_column\":{},\"rule\":{},\"severity\":{},\
+                 \"message\":{},\"fix\":{},\"opened\":{}}}",
+                finding.at.line,
+                finding.at.column,
+                finding.end.column,
+                json_string(finding.rule()),
+                json_string(&finding.severity().to_string()),
+                json_string(&finding.message),
+                json_string(&finding.fix),
+                opened
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+
+    writeln!(
+        out,
+        "{{\"findings\":[{}],\"syntax_score\":{}}}",
+        findings, report.syntax_score
+    )
+}
+
+// Enough of SARIF 2.1.0 for review tools to place each finding in the file
+pub fn write_sarif(out: &mut impl Write, report: &Report, uri: &str) -> Result<()> {
+    let region = |at: &Location, end: &Location| {
+        format!(
+            "{{\"physicalLocation\":{{\"artifactLocation\":{{\"uri\":{}}},\
+             \"region\":{{\"startLine\":{},\"startColumn\":{},\"endLine\":{},\"endColumn\":{}}}}}}}",
+            json_string(uri),
+            at.line,
+            at.column,
+            end.line,
+            end.column + 1
+        )
+    };
+
+    let results = report
+        .findings
+        .iter()
+        .map(|finding| {
+            let related = match &finding.opened {
+                Some(opened) => format!(",\"relatedLocations\":[{}]", region(opened, opened)),
+                None => String::new(),
+            };
+
+            format!(
+                "{{\"ruleId\":{},\"level\":{},\"message\":{{\"text\":{}}},\"locations\":[{}]{},\
+                 \"fixes\":[{{\"description\":{{\"text\":{}}}}}]}}",
+                json_string(finding.rule()),
+                json_string(&finding.severity().to_string()),
+                json_string(&finding.message),
+                region(&finding.at, &finding.end),
+                related,
+                json_string(&finding.fix)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+
+    writeln!(
+        out,
+        "{{\"version\":\"2.1.0\",\"runs\":[{{\"tool\":{{\"driver\":{{\"name\":\"bracket-lint\"}}}},\"results\":[{}]}}]}}",
+        results
+    )
+}
+
+pub fn write_report(out: &mut impl Write, report: &Report, format: &ReportFormat) -> Result<()> {
+    match format {
+        ReportFormat::Terminal => write_terminal(out, report),
+        ReportFormat::Plain => write_plain(out, report),
+        ReportFormat::Json => write_json(out, report),
+        ReportFormat::Sarif { uri } => write_sarif(out, report, uri),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(report: &Report, format: ReportFormat) -> String {
+        let mut out = vec![];
+
+        write_report(&mut out, report, &format).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_plain() {
+        let report = Report::lint_lines("()\n{([(<{}[<>[]}>{[]\n(]", &PairTable::standard());
+
+        assert_eq!(
+            render(&report, ReportFormat::Plain),
+            "2:13: error: expected ] to close [, found } (insert ]>)]) at 2:13)\n\
+             2:14: error: > closes nothing (delete > at 2:14)\n\
+             2:15: warning: { is never closed (insert } at 2:18)\n\
+             3:2: error: expected ) to close (, found ] (delete ] at 3:2)\n\
+             3:1: warning: ( is never closed (insert ) at 3:3)\n\
+             3 errors, 2 warnings, syntax score 1254\n"
+        );
+    }
+
+    #[test]
+    fn test_source_positions() {
+        let report = Report::lint_lines("    let x = f(a, b]];", &PairTable::c_like());
+        let columns: Vec<(usize, usize)> = report
+            .findings
+            .iter()
+            .map(|finding| (finding.at.column, finding.end.column))
+            .collect();
+
+        assert_eq!(columns, vec![(19, 19), (20, 20), (14, 14)]);
+        assert_eq!(
+            report.findings[0].opened,
+            Some(Location {
+                line: 1,
+                column: 14
+            })
+        );
+
+        let report = Report::lint_source(
+            "fn main() {\n    foo(\"}\");\n    bar(x));\n",
+            &PairTable::c_like(),
+        );
+
+        assert_eq!(
+            render(&report, ReportFormat::Plain),
+            "3:11: error: expected } to close {, found ), opened at 1:11 (delete ) at 3:11)\n\
+             1:11: warning: { is never closed (insert } at 3:12)\n\
+             1 errors, 1 warnings, syntax score 3\n"
+        );
+    }
+
+    #[test]
+    fn test_custom_pairs() {
+        let table = PairTable::new().pair("begin", "end");
+        let report = Report::lint_lines("x := begin y end end", &table);
+
+        assert_eq!(
+            render(&report, ReportFormat::Plain),
+            "1:18: error: end closes nothing (delete end at 1:18)\n\
+             1 errors, 0 warnings, syntax score 0\n"
+        );
+        assert_eq!(report.findings[0].end.column, 20);
+    }
+
+    #[test]
+    fn test_terminal() {
+        let report = Report::lint_lines("<{]", &PairTable::standard());
+        let output = render(&report, ReportFormat::Terminal);
+        let underlines: Vec<&str> = output.lines().skip(1).take(3).collect();
+
+        assert!(output.starts_with("   1 | <{]\n"));
+        assert!(underlines[0].starts_with("     |  \x1b[1;31m^^ error:"));
+        assert!(underlines[1].starts_with("     |  \x1b[1;33m^ warning:"));
+        assert!(underlines[2].starts_with("     | \x1b[1;33m^ warning:"));
+    }
+
+    #[test]
+    fn test_json() {
+        let report = Report::lint_lines("(]", &PairTable::standard());
+
+        assert_eq!(
+            render(&report, ReportFormat::Json),
+            "{\"findings\":[\
+             {\"line\":1,\"column\":2,\"end_column\":2,\"rule\":\"mismatched-closer\",\
+             \"severity\":\"error\",\"message\":\"expected ) to close (, found ]\",\
+             \"fix\":\"delete ] at 1:2\",\"opened\":{\"line\":1,\"column\":1}},\
+             {\"line\":1,\"column\":1,\"end_column\":1,\"rule\":\"unclosed-opener\",\
+             \"severity\":\"warning\",\"message\":\"( is never closed\",\
+             \"fix\":\"insert ) at 1:3\",\"opened\":null}\
+             ],\"syntax_score\":57}\n"
+        );
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\n\"");
+    }
+
+    #[test]
+    fn test_sarif() {
+        let report = Report::lint_lines("f(a, b]];", &PairTable::c_like());
+        let output = render(
+            &report,
+            ReportFormat::Sarif {
+                uri: "src/main.c".to_string(),
+            },
+        );
+
+        assert!(output.starts_with("{\"version\":\"2.1.0\""));
+        assert!(output.contains("\"ruleId\":\"mismatched-closer\",\"level\":\"error\""));
+        assert!(output.contains(
+            "\"artifactLocation\":{\"uri\":\"src/main.c\"},\
+             \"region\":{\"startLine\":1,\"startColumn\":7,\"endLine\":1,\"endColumn\":8}"
+        ));
+        assert!(output.contains("\"startColumn\":8,"));
+    }
+
+    #[test]
+    fn test_syntax_score() {
+        let sample = "[({(<(())[]>[[{[]{<()<>>\n\
+                      [(()[<>])]({[<{<<[]>>(\n\
+                      {([(<{}[<>[]}>{[]{[(<()>\n\
+                      (((({<>}<{<{<>}{[]{[]{}\n\
+                      [[<[([]))<([[{}[[()]]]\n\
+                      [{[{({}]{}}([{[{{{}}([]\n\
+                      {<[[]]>}<{[{[{[]{()[[[]\n\
+                      [<(<(<(<{}))><([]([]()\n\
+                      <{([([[(<>()){}]>(<<{{\n\
+                      <{([{{}}[<[[[<>{}]]]>[]]";
+
+        assert_eq!(
+            Report::lint_lines(sample, &PairTable::standard()).syntax_score,
+            26397
+        );
+
+        let report = Report::lint_lines(")", &PairTable::standard());
+
+        assert_eq!(report.summary(), "1 errors, 0 warnings, syntax score 3");
+    }
+
+    #[test]
+    fn test_lint_brackets() {
+        let table = PairTable::new().pair("begin", "end");
+        let lines = vec![
+            crate::bracket::parse_line("(]"),
+            table.parse_line("begin end end"),
+        ];
+        let report = Report::lint_brackets(&lines, &table);
+
+        assert_eq!(report.source, "(]\nbeginendend");
+        assert_eq!(
+            render(&report, ReportFormat::Plain),
+            "1:2: error: expected ) to close (, found ] (delete ] at 1:2)\n\
+             1:1: warning: ( is never closed (insert ) at 1:3)\n\
+             2:9: error: end closes nothing (delete end at 2:9)\n\
+             2 errors, 1 warnings, syntax score 57\n"
+        );
+    }
+}
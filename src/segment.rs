@@ -1,4 +1,6 @@
+use crate::display::DisplayDefinition;
 use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
 use std::iter::FromIterator;
 
 #[derive(PartialEq, Clone, Debug)]
@@ -27,6 +29,16 @@ impl From<&str> for Digit {
     }
 }
 
+impl From<&Digit> for String {
+    fn from(digit: &Digit) -> Self {
+        let mut letters: Vec<char> = digit.letters.iter().map(char::from).collect();
+
+        letters.sort_unstable();
+
+        letters.into_iter().collect()
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Hash)]
 pub enum Segment {
     A,
@@ -40,6 +52,30 @@ pub enum Segment {
 
 impl Eq for Segment {}
 
+impl From<&Segment> for char {
+    fn from(segment: &Segment) -> Self {
+        match segment {
+            Segment::A => 'a',
+            Segment::B => 'b',
+            Segment::C => 'c',
+            Segment::D => 'd',
+            Segment::E => 'e',
+            Segment::F => 'f',
+            Segment::G => 'g',
+        }
+    }
+}
+
+const ALL_SEGMENTS: [Segment; 7] = [
+    Segment::A,
+    Segment::B,
+    Segment::C,
+    Segment::D,
+    Segment::E,
+    Segment::F,
+    Segment::G,
+];
+
 #[derive(Debug, PartialEq, Clone, Hash)]
 pub struct Wiring {
     wire_to: [Segment; 7],
@@ -47,6 +83,21 @@ pub struct Wiring {
 
 impl Eq for Wiring {}
 
+impl Display for Wiring {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let row = |segments: &[Segment]| {
+            segments
+                .iter()
+                .map(|s| char::from(s).to_string())
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+
+        writeln!(f, "real | {}", row(&ALL_SEGMENTS))?;
+        write!(f, "wire | {}", row(&self.wire_to))
+    }
+}
+
 impl Wiring {
     pub fn get_mask(&self, segments: &HashSet<Segment>) -> u64 {
         let two = 2u64;
@@ -167,7 +218,58 @@ fn filter_possible_by_rewiring(possibles: &[Wiring], digit: &Digit) -> Vec<Wirin
     Vec::from_iter(filtered)
 }
 
+fn render_row(digits: &[Digit], wiring: Option<&Wiring>) -> Vec<String> {
+    let rendered: Vec<Vec<String>> = digits.iter().map(|d| d.render(wiring)).collect();
+
+    (0..7)
+        .map(|line| {
+            rendered
+                .iter()
+                .map(|digit| digit[line].as_str())
+                .collect::<Vec<&str>>()
+                .join("  ")
+        })
+        .collect()
+}
+
 impl Digit {
+    // Draws the digit as below, labelling each lit segment with the scrambled letter
+    // driving it. Without a wiring the letters are drawn where they would be unscrambled.
+    //  aaaa
+    // b    c
+    // b    c
+    //  dddd
+    // e    f
+    // e    f
+    //  gggg
+    pub fn render(&self, wiring: Option<&Wiring>) -> Vec<String> {
+        let mut labels = [' '; 7];
+
+        for letter in &self.letters {
+            let index = match wiring {
+                Some(wiring) => wiring.get_index_for(letter),
+                None => ALL_SEGMENTS.iter().position(|s| s == letter),
+            };
+
+            if let Some(index) = index {
+                labels[index] = char::from(letter);
+            }
+        }
+
+        let bar = |label: char| format!(" {} ", label.to_string().repeat(4));
+        let sides = |left: char, right: char| format!("{}    {}", left, right);
+
+        vec![
+            bar(labels[0]),
+            sides(labels[1], labels[2]),
+            sides(labels[1], labels[2]),
+            bar(labels[3]),
+            sides(labels[4], labels[5]),
+            sides(labels[4], labels[5]),
+            bar(labels[6]),
+        ]
+    }
+
     pub fn easy_guess(&self) -> Option<u64> {
         match self.letters.len() {
             2 => {
@@ -213,6 +315,55 @@ impl Digit {
     }
 }
 
+#[derive(PartialEq, Clone, Debug)]
+pub enum DecodeError {
+    UnknownSegment(char),
+    UnplaceablePattern(String),
+    AmbiguousOutput(Vec<u64>),
+    AmbiguousGlyphs(Vec<String>),
+    TooManySegments(usize),
+    DuplicateSegment(char),
+    SharedPattern(char, char),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::UnknownSegment(letter) => {
+                write!(f, "Unknown segment {}", letter)
+            }
+            DecodeError::UnplaceablePattern(pattern) => {
+                write!(f, "No wiring can place the pattern {}", pattern)
+            }
+            DecodeError::AmbiguousOutput(values) => {
+                write!(f, "Consistent wirings disagree on the output: {:?}", values)
+            }
+            DecodeError::AmbiguousGlyphs(glyphs) => {
+                write!(f, "Consistent wirings disagree on the glyphs: {:?}", glyphs)
+            }
+            DecodeError::TooManySegments(count) => {
+                write!(f, "{} segments don't fit in a 32 bit mask", count)
+            }
+            DecodeError::DuplicateSegment(letter) => {
+                write!(f, "Segment {} is named twice", letter)
+            }
+            DecodeError::SharedPattern(first, second) => {
+                write!(f, "Glyphs {} and {} light the same segments", first, second)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Decoded {
+    pub value: u64,
+    pub wirings: usize,
+    // First of the consistent wirings, they all give `value`
+    pub wiring: Wiring,
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub struct Entry {
     pub digits: Vec<Digit>,
@@ -227,7 +378,7 @@ impl From<&str> for Entry {
             .collect();
 
         Entry {
-            digits: parts.get(0).unwrap().clone(),
+            digits: parts.first().unwrap().clone(),
             output: parts.get(1).unwrap().clone(),
         }
     }
@@ -235,7 +386,150 @@ impl From<&str> for Entry {
 
 impl Entry {
     pub fn count_easily_guessed_outputs(&self) -> usize {
-        self.output.iter().map(|d| d.easy_guess()).flatten().count()
+        self.output.iter().filter_map(|d| d.easy_guess()).count()
+    }
+
+    // Across the ten digits each real segment lights up a distinct number of times,
+    // except a/c (8) and d/g (7). c is part of 1 and a is not, d is part of 4 and g
+    // is not, which settles the rest.
+    pub fn deduce_wiring(&self) -> Option<Wiring> {
+        let one = self.digits.iter().find(|d| d.letters.len() == 2)?;
+        let four = self.digits.iter().find(|d| d.letters.len() == 4)?;
+        let mut wire_to: [Option<Segment>; 7] = [None; 7];
+
+        for scrambled in ALL_SEGMENTS {
+            let frequency = self
+                .digits
+                .iter()
+                .filter(|d| d.letters.contains(&scrambled))
+                .count();
+
+            let real = match (
+                frequency,
+                one.letters.contains(&scrambled),
+                four.letters.contains(&scrambled),
+            ) {
+                (8, false, _) => 0,
+                (6, _, _) => 1,
+                (8, true, _) => 2,
+                (7, _, true) => 3,
+                (4, _, _) => 4,
+                (9, _, _) => 5,
+                (7, _, false) => 6,
+                _ => return None,
+            };
+
+            if wire_to[real].replace(scrambled).is_some() {
+                return None;
+            }
+        }
+
+        let wiring = Wiring {
+            wire_to: [
+                wire_to[0]?,
+                wire_to[1]?,
+                wire_to[2]?,
+                wire_to[3]?,
+                wire_to[4]?,
+                wire_to[5]?,
+                wire_to[6]?,
+            ],
+        };
+
+        self.decode(&wiring)?;
+
+        Some(wiring)
+    }
+
+    pub fn consistent_wirings(&self) -> Result<Vec<Wiring>, DecodeError> {
+        let definition = DisplayDefinition::seven_segment_decimal();
+        let patterns = self
+            .digits
+            .iter()
+            .chain(self.output.iter())
+            .map(|digit| definition.pattern(&String::from(digit)))
+            .collect::<Result<Vec<u32>, DecodeError>>()?;
+
+        Ok(definition
+            .solve_or_blame(&patterns)?
+            .iter()
+            .map(|rewiring| {
+                let mut wire_to = ALL_SEGMENTS;
+
+                for (scrambled, real) in rewiring.real_for.iter().enumerate() {
+                    wire_to[*real] = ALL_SEGMENTS[scrambled];
+                }
+
+                Wiring { wire_to }
+            })
+            .collect())
+    }
+
+    // A full set of ten patterns pins down a single wiring, so the deduction is
+    // tried first and the permutations are only searched when it fails
+    pub fn solve(&self) -> Result<Decoded, DecodeError> {
+        if let Some(wiring) = self.deduce_wiring() {
+            if let Some(value) = self.decode(&wiring) {
+                return Ok(Decoded {
+                    value,
+                    wirings: 1,
+                    wiring,
+                });
+            }
+        }
+
+        let mut wirings = self.consistent_wirings()?;
+        let mut values: Vec<u64> = wirings.iter().filter_map(|w| self.decode(w)).collect();
+
+        values.sort_unstable();
+        values.dedup();
+
+        match values[..] {
+            [value] => Ok(Decoded {
+                value,
+                wirings: wirings.len(),
+                wiring: wirings.swap_remove(0),
+            }),
+            _ => Err(DecodeError::AmbiguousOutput(values)),
+        }
+    }
+
+    pub fn render(&self, wiring: Option<&Wiring>) -> String {
+        render_row(&self.digits, wiring)
+            .iter()
+            .zip(render_row(&self.output, wiring).iter())
+            .map(|(digits, output)| format!("{} | {}", digits, output).trim_end().to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn explain(&self) -> Result<String, DecodeError> {
+        let decoded = self.solve()?;
+        let output = render_row(&self.output, Some(&decoded.wiring))
+            .iter()
+            .map(|line| line.trim_end().to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let wirings = match decoded.wirings {
+            1 => "1 consistent wiring".to_string(),
+            n => format!("{} consistent wirings", n),
+        };
+
+        Ok(format!(
+            "{}\n\n{}\n\n= {} ({})",
+            output, decoded.wiring, decoded.value, wirings
+        ))
+    }
+
+    pub fn decode(&self, wiring: &Wiring) -> Option<u64> {
+        for digit in &self.digits {
+            digit.wire(wiring)?;
+        }
+
+        self.output
+            .iter()
+            .try_fold(0, |value, digit| Some(value * 10 + digit.wire(wiring)?))
     }
 }
 
@@ -255,14 +549,18 @@ impl Entries {
     pub fn count_easily_guessed_outputs(&self) -> usize {
         self.entries
             .iter()
-            .map(|entry| {
-                entry
-                    .output
-                    .iter()
-                    .map(|d| d.easy_guess())
-                    .flatten()
-                    .count()
-            })
+            .map(|entry| entry.output.iter().filter_map(|d| d.easy_guess()).count())
+            .sum()
+    }
+
+    pub fn solve_each(&self) -> Vec<Result<Decoded, DecodeError>> {
+        self.entries.iter().map(Entry::solve).collect()
+    }
+
+    pub fn sum_deduced_output(&self) -> Option<u64> {
+        self.entries
+            .iter()
+            .map(|entry| entry.decode(&entry.deduce_wiring()?))
             .sum()
     }
 
@@ -308,7 +606,7 @@ impl Entries {
             }
 
             // Expect at least one correct wiring to be left
-            let correct_wiring = possibles.get(0)?;
+            let correct_wiring = possibles.first()?;
 
             let output_count = entry.output.len();
 
@@ -466,7 +764,7 @@ mod tests {
         };
 
         let filtered = filter_possible_using_data(
-            &vec![no.clone(), yes.clone()],
+            &[no.clone(), yes.clone()],
             &HashSet::from([Segment::A, Segment::B]),
             &HashSet::from([Segment::C, Segment::F]),
         );
@@ -489,4 +787,148 @@ mod tests {
 
         assert_eq!(entries.count_real_output(), Some(61229));
     }
+
+    #[test]
+    fn test_deduce_wiring() {
+        let entry = Entry::from(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf",
+        );
+        let wiring = entry.deduce_wiring().unwrap();
+
+        assert_eq!(
+            wiring,
+            Wiring {
+                wire_to: [
+                    Segment::D,
+                    Segment::E,
+                    Segment::A,
+                    Segment::F,
+                    Segment::G,
+                    Segment::B,
+                    Segment::C
+                ]
+            }
+        );
+        assert_eq!(entry.decode(&wiring), Some(5353));
+        assert_eq!(
+            Entry::from("ab ab ab ab ab ab ab ab ab ab | ab").deduce_wiring(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_deduced_output() {
+        let entries = Entries::from("be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe\n\
+                                        edbfga begcd cbg gc gcadebf fbgde acbgfd abcde gfcbed gfec | fcgedb cgb dgebacf gc\n\
+                                        fgaebd cg bdaec gdafb agbcfd gdcbef bgcad gfac gcb cdgabef | cg cg fdcagb cbg\n\
+                                        fbegcd cbd adcefb dageb afcb bc aefdc ecdab fgdeca fcdbega | efabcd cedba gadfec cb\n\
+                                        aecbfdg fbg gf bafeg dbefa fcge gcbea fcaegb dgceab fcbdga | gecf egdcabf bgf bfgea\n\
+                                        fgeab ca afcebg bdacfeg cfaedg gcfdb baec bfadeg bafgc acf | gebdcfa ecba ca fadegcb\n\
+                                        dbcfg fgd bdegcaf fgec aegbdf ecdfab fbedc dacgb gdcebf gf | cefg dcbef fcge gbcadfe\n\
+                                        bdfegc cbegaf gecbf dfcage bdacg ed bedf ced adcbefg gebcd | ed bcgafe cdgba cbgef\n\
+                                        egadfb cdbfeg cegd fecab cgb gbdefca cg fgcdab egfdb bfceg | gbdfcae bgc cg cgb\n\
+                                        gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce");
+
+        assert_eq!(entries.sum_deduced_output(), Some(61229));
+        assert_eq!(entries.sum_deduced_output(), entries.count_real_output());
+
+        let entries = Entries::from(include_str!("../input/2021/day8.txt"));
+
+        assert_eq!(entries.sum_deduced_output(), Some(994266));
+        assert_eq!(entries.sum_deduced_output(), entries.count_real_output());
+    }
+
+    #[test]
+    fn test_solve_each() {
+        let entries = Entries::from(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf\n\
+             ab abc abcd | abcd ab\n\
+             ab ac | ab\n\
+             ab abc | abc ab",
+        );
+
+        let results = entries.solve_each();
+        let first = &entries.entries[0];
+
+        assert_eq!(
+            results[0],
+            Ok(Decoded {
+                value: 5353,
+                wirings: 1,
+                wiring: first.deduce_wiring().unwrap()
+            })
+        );
+        assert_eq!(
+            results[1],
+            Err(DecodeError::UnplaceablePattern("abcd".to_string()))
+        );
+        assert_eq!(
+            results[2],
+            Err(DecodeError::UnplaceablePattern("ac".to_string()))
+        );
+        let last = results[3].as_ref().unwrap();
+
+        assert_eq!((last.value, last.wirings), (71, 48));
+        assert_eq!(entries.entries[3].decode(&last.wiring), Some(71));
+        assert_eq!(
+            Entry::from("abcde | abcde").solve(),
+            Err(DecodeError::AmbiguousOutput(vec![2, 3, 5]))
+        );
+    }
+
+    #[test]
+    fn test_render_digit() {
+        let digit = Digit::from("ab");
+
+        assert_eq!(
+            digit.render(None),
+            vec![" aaaa ", "b     ", "b     ", "      ", "      ", "      ", "      "]
+        );
+
+        let entry = Entry::from(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf",
+        );
+        let wiring = entry.deduce_wiring().unwrap();
+
+        assert_eq!(
+            digit.render(Some(&wiring)),
+            vec!["      ", "     a", "     a", "      ", "     b", "     b", "      "]
+        );
+    }
+
+    #[test]
+    fn test_render_entry() {
+        let entry = Entry::from("ab dab | ab");
+
+        assert_eq!(
+            entry.render(None),
+            [
+                " aaaa    aaaa  |  aaaa",
+                "b       b      | b",
+                "b       b      | b",
+                "         dddd  |",
+                "               |",
+                "               |",
+                "               |",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_explain() {
+        let entry = Entry::from(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf",
+        );
+        let explained = entry.explain().unwrap();
+
+        assert!(explained
+            .starts_with(" dddd    dddd    dddd    dddd\ne            a  e            a\n"));
+        assert!(explained.contains("real | a b c d e f g\nwire | d e a f g b c"));
+        assert!(explained.ends_with("= 5353 (1 consistent wiring)"));
+
+        let explained = Entry::from("ab abc | abc ab").explain().unwrap();
+
+        assert!(explained.ends_with("= 71 (48 consistent wirings)"));
+    }
 }
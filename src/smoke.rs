@@ -1,4 +1,174 @@
-use std::collections::HashMap;
+use anyhow::Result;
+use png_encode_mini::write_rgba_from_u8;
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+impl Connectivity {
+    fn offsets(&self) -> &'static [(i64, i64)] {
+        match self {
+            Connectivity::Four => &[(0, -1), (1, 0), (0, 1), (-1, 0)],
+            Connectivity::Eight => &[
+                (0, -1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+                (0, 1),
+                (-1, 1),
+                (-1, 0),
+                (-1, -1),
+            ],
+        }
+    }
+}
+
+// How a flat-bottomed valley, several equally low tiles next to each other, is reported
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Plateau {
+    Region,
+    Representative,
+    Ignore,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Wall {
+    AtLeast(u32),
+    Predicate(fn(u32) -> bool),
+}
+
+impl Wall {
+    pub fn is_wall(&self, height: u32) -> bool {
+        match self {
+            Wall::AtLeast(threshold) => height >= *threshold,
+            Wall::Predicate(predicate) => predicate(height),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BasinOptions {
+    pub connectivity: Connectivity,
+    pub plateau: Plateau,
+    pub wall: Wall,
+}
+
+impl Default for BasinOptions {
+    fn default() -> Self {
+        BasinOptions {
+            connectivity: Connectivity::Four,
+            plateau: Plateau::Ignore,
+            wall: Wall::AtLeast(9),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Basins {
+    pub labels: Vec<Vec<Option<usize>>>,
+    pub sizes: Vec<usize>,
+    pub low_points: Vec<(usize, usize)>,
+}
+
+// Where water goes: every tile drains to its lowest strictly lower neighbour,
+// flat tiles drain along the shortest way off their plateau, and each low
+// region collects into a single sink
+#[derive(Debug, PartialEq)]
+pub struct Drainage {
+    pub flow: Vec<Vec<Option<(usize, usize)>>>,
+    // Tiles that had more than one equally good way to drain
+    pub ambiguous: Vec<Vec<bool>>,
+    pub accumulation: Vec<Vec<usize>>,
+    pub sinks: Vec<(usize, usize)>,
+    pub catchment: Vec<Vec<usize>>,
+}
+
+const CATCHMENT_PALETTE: [[u8; 3]; 8] = [
+    [230, 25, 75],
+    [60, 180, 75],
+    [255, 225, 25],
+    [0, 130, 200],
+    [245, 130, 48],
+    [145, 30, 180],
+    [70, 240, 240],
+    [240, 50, 230],
+];
+
+impl Drainage {
+    pub fn catchment_sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![0; self.sinks.len()];
+
+        for sink in self.catchment.iter().flatten() {
+            sizes[*sink] += 1;
+        }
+
+        sizes
+    }
+
+    // Each catchment in its own colour, sinks black and ambiguous tiles white
+    pub fn dump(&self, filename: &str) -> Result<()> {
+        let mut f = std::fs::File::create(filename)?;
+        let mut image: Vec<u8> = vec![];
+
+        for (y, row) in self.catchment.iter().enumerate().rev() {
+            for (x, sink) in row.iter().enumerate() {
+                let [r, g, b] = if self.flow[y][x].is_none() {
+                    [0, 0, 0]
+                } else if self.ambiguous[y][x] {
+                    [255, 255, 255]
+                } else {
+                    CATCHMENT_PALETTE[sink % CATCHMENT_PALETTE.len()]
+                };
+
+                image.extend([r, g, b, 255]);
+            }
+        }
+
+        write_rgba_from_u8(
+            &mut f,
+            &image,
+            self.catchment.first().map_or(0, |row| row.len()) as u32,
+            self.catchment.len() as u32,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Drainage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let arrows = self
+            .flow
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(x, to)| match to {
+                        None => 'o',
+                        Some(_) if self.ambiguous[y][x] => '?',
+                        Some((t_x, t_y)) => {
+                            match (*t_x as i64 - x as i64, *t_y as i64 - y as i64) {
+                                (0, -1) => '^',
+                                (1, 0) => '>',
+                                (0, 1) => 'v',
+                                (-1, 0) => '<',
+                                (1, -1) | (-1, 1) => '/',
+                                _ => '\\',
+                            }
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        write!(f, "{}", arrows)
+    }
+}
 
 #[derive(Debug, PartialEq)]
 pub struct Map {
@@ -7,6 +177,24 @@ pub struct Map {
     height: usize,
 }
 
+// Deep blue through green to a pale summit across the 0-9 range
+pub fn height_colour(height: u32) -> [u8; 3] {
+    const STOPS: [[u8; 3]; 4] = [[8, 48, 107], [35, 139, 69], [166, 118, 29], [245, 245, 245]];
+
+    let t = height.min(9) as f64 / 9.0 * (STOPS.len() - 1) as f64;
+    let i = (t.floor() as usize).min(STOPS.len() - 2);
+    let f = t - i as f64;
+
+    let mut colour = [0; 3];
+
+    for c in 0..3 {
+        colour[c] =
+            (STOPS[i][c] as f64 + (STOPS[i + 1][c] as f64 - STOPS[i][c] as f64) * f).round() as u8;
+    }
+
+    colour
+}
+
 pub fn risk_level(height: u32) -> u32 {
     height + 1
 }
@@ -34,27 +222,38 @@ impl From<&str> for Map {
 }
 
 impl Map {
+    fn tile(&self, x: i64, y: i64) -> Option<u32> {
+        if x < 0 || y < 0 {
+            return None;
+        }
+
+        self.tiles
+            .get(y as usize)
+            .and_then(|r| r.get(x as usize))
+            .copied()
+    }
+
     pub fn peek(&self, x: usize, y: usize) -> Option<Window> {
-        let north = if y > 0 {
-            self.tiles.get(y - 1).and_then(|r| r.get(x)).copied()
-        } else {
-            None
-        };
-        let east = self.tiles.get(y).and_then(|r| r.get(x + 1)).copied();
-        let south = self.tiles.get(y + 1).and_then(|r| r.get(x)).copied();
-        let west = if x > 0 {
-            self.tiles.get(y).and_then(|r| r.get(x - 1)).copied()
-        } else {
-            None
+        self.peek_with(x, y, Connectivity::Four)
+    }
+
+    pub fn peek_with(&self, x: usize, y: usize, connectivity: Connectivity) -> Option<Window> {
+        let (x, y) = (x as i64, y as i64);
+        let diagonal = |d_x: i64, d_y: i64| match connectivity {
+            Connectivity::Four => None,
+            Connectivity::Eight => self.tile(x + d_x, y + d_y),
         };
-        let center = *self.tiles.get(y).and_then(|r| r.get(x))?;
 
         Some(Window {
-            north,
-            east,
-            south,
-            west,
-            center,
+            north: self.tile(x, y - 1),
+            north_east: diagonal(1, -1),
+            east: self.tile(x + 1, y),
+            south_east: diagonal(1, 1),
+            south: self.tile(x, y + 1),
+            south_west: diagonal(-1, 1),
+            west: self.tile(x - 1, y),
+            north_west: diagonal(-1, -1),
+            center: self.tile(x, y)?,
         })
     }
 
@@ -118,72 +317,462 @@ impl Map {
         visited
     }
 
-    pub fn find_basins(&self) -> Vec<usize> {
-        let mut basins = vec![];
-        let mut visited = HashMap::new();
+    fn neighbours(&self, x: usize, y: usize, connectivity: Connectivity) -> Vec<(usize, usize)> {
+        connectivity
+            .offsets()
+            .iter()
+            .map(|(d_x, d_y)| (x as i64 + d_x, y as i64 + d_y))
+            .filter(|(n_x, n_y)| self.tile(*n_x, *n_y).is_some())
+            .map(|(n_x, n_y)| (n_x as usize, n_y as usize))
+            .collect()
+    }
+
+    // Connected regions of equal height whose every neighbour is higher. A single
+    // tile region is an ordinary low point, larger ones are plateaus.
+    pub fn find_low_regions(&self, connectivity: Connectivity) -> Vec<Vec<(usize, usize)>> {
+        let mut seen = vec![vec![false; self.width]; self.height];
+        let mut regions = vec![];
 
         for y in 0..self.height {
             for x in 0..self.width {
-                if visited.contains_key(&(x, y)) {
+                if seen[y][x] {
                     continue;
                 }
 
-                let detected = self.detect_basin(x, y, &HashMap::new());
+                let level = self.tiles[y][x];
+                let mut region = vec![];
+                let mut lowest = true;
+                let mut queue = VecDeque::from([(x, y)]);
+
+                seen[y][x] = true;
 
-                let mut total = 0;
+                while let Some((q_x, q_y)) = queue.pop_front() {
+                    region.push((q_x, q_y));
 
-                for ((d_x, d_y), in_basin) in &detected {
-                    visited.insert((*d_x, *d_y), *in_basin);
+                    for (n_x, n_y) in self.neighbours(q_x, q_y, connectivity) {
+                        let n = self.tiles[n_y][n_x];
 
-                    if *in_basin {
-                        total += 1;
+                        if n < level {
+                            lowest = false;
+                        } else if n == level && !seen[n_y][n_x] {
+                            seen[n_y][n_x] = true;
+                            queue.push_back((n_x, n_y));
+                        }
                     }
                 }
 
-                if total > 0 {
-                    basins.push(total);
+                if lowest {
+                    region.sort_unstable_by_key(|(r_x, r_y)| (*r_y, *r_x));
+                    regions.push(region);
                 }
             }
         }
 
-        basins
+        regions
+    }
+
+    pub fn find_low_points_with(&self, options: &BasinOptions) -> Vec<(usize, usize)> {
+        self.find_low_regions(options.connectivity)
+            .into_iter()
+            .flat_map(|region| match (region.len(), options.plateau) {
+                (1, _) | (_, Plateau::Region) => region,
+                (_, Plateau::Representative) => vec![region[0]],
+                (_, Plateau::Ignore) => vec![],
+            })
+            .collect()
+    }
+
+    pub fn label_basins(&self) -> Basins {
+        self.label_basins_with(&BasinOptions::default())
+    }
+
+    // Breadth-first flood fill from every unlabelled non-wall tile, in reading order
+    pub fn label_basins_with(&self, options: &BasinOptions) -> Basins {
+        let mut labels = vec![vec![None; self.width]; self.height];
+        let mut sizes = vec![];
+        let mut low_points = vec![];
+        let mut queue = VecDeque::new();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if labels[y][x].is_some() || options.wall.is_wall(self.tiles[y][x]) {
+                    continue;
+                }
+
+                let label = sizes.len();
+                let mut size = 0;
+                let mut low_point = (x, y);
+
+                labels[y][x] = Some(label);
+                queue.push_back((x, y));
+
+                while let Some((q_x, q_y)) = queue.pop_front() {
+                    size += 1;
+
+                    if self.tiles[q_y][q_x] < self.tiles[low_point.1][low_point.0] {
+                        low_point = (q_x, q_y);
+                    }
+
+                    for (n_x, n_y) in self.neighbours(q_x, q_y, options.connectivity) {
+                        if labels[n_y][n_x].is_none() && !options.wall.is_wall(self.tiles[n_y][n_x])
+                        {
+                            labels[n_y][n_x] = Some(label);
+                            queue.push_back((n_x, n_y));
+                        }
+                    }
+                }
+
+                sizes.push(size);
+                low_points.push(low_point);
+            }
+        }
+
+        Basins {
+            labels,
+            sizes,
+            low_points,
+        }
+    }
+
+    pub fn find_basins(&self) -> Vec<usize> {
+        self.label_basins().sizes
+    }
+
+    pub fn drainage(&self, connectivity: Connectivity) -> Drainage {
+        let mut flow = vec![vec![None; self.width]; self.height];
+        let mut ambiguous = vec![vec![false; self.width]; self.height];
+        // Steps to the nearest tile that can drain downhill, or to the sink
+        let mut distance = vec![vec![None; self.width]; self.height];
+        let mut queue = VecDeque::new();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let lower: Vec<(usize, usize)> = self
+                    .neighbours(x, y, connectivity)
+                    .into_iter()
+                    .filter(|(n_x, n_y)| self.tiles[*n_y][*n_x] < self.tiles[y][x])
+                    .collect();
+                let lowest = lower.iter().map(|(n_x, n_y)| self.tiles[*n_y][*n_x]).min();
+
+                if let Some(lowest) = lowest {
+                    let mut candidates = lower
+                        .iter()
+                        .filter(|(n_x, n_y)| self.tiles[*n_y][*n_x] == lowest);
+
+                    flow[y][x] = candidates.next().copied();
+                    ambiguous[y][x] = candidates.next().is_some();
+                    distance[y][x] = Some(0);
+                    queue.push_back((x, y));
+                }
+            }
+        }
+
+        let sinks: Vec<(usize, usize)> = self
+            .find_low_regions(connectivity)
+            .into_iter()
+            .map(|region| region[0])
+            .collect();
+
+        for (x, y) in &sinks {
+            distance[*y][*x] = Some(0);
+            queue.push_back((*x, *y));
+        }
+
+        // Flat tiles drain towards whichever level neighbour is a step closer to
+        // a way down, plateaus in low regions towards their sink
+        while let Some((q_x, q_y)) = queue.pop_front() {
+            let step = distance[q_y][q_x].map(|d| d + 1);
+
+            for (n_x, n_y) in self.neighbours(q_x, q_y, connectivity) {
+                if self.tiles[n_y][n_x] != self.tiles[q_y][q_x] {
+                    continue;
+                }
+
+                if distance[n_y][n_x].is_none() {
+                    distance[n_y][n_x] = step;
+                    flow[n_y][n_x] = Some((q_x, q_y));
+                    queue.push_back((n_x, n_y));
+                } else if distance[n_y][n_x] == step {
+                    ambiguous[n_y][n_x] = true;
+                }
+            }
+        }
+
+        // Water only runs downhill or off a plateau, so visiting tiles from the top
+        // down and from the far side of each plateau means each tile's total is
+        // final before it is passed on
+        let mut by_height: Vec<(usize, usize)> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .collect();
+
+        by_height.sort_by_key(|(x, y)| std::cmp::Reverse((self.tiles[*y][*x], distance[*y][*x])));
+
+        let mut accumulation = vec![vec![1; self.width]; self.height];
+
+        for (x, y) in &by_height {
+            if let Some((t_x, t_y)) = flow[*y][*x] {
+                accumulation[t_y][t_x] += accumulation[*y][*x];
+            }
+        }
+
+        let mut catchment = vec![vec![0; self.width]; self.height];
+
+        for (i, (x, y)) in sinks.iter().enumerate() {
+            catchment[*y][*x] = i;
+        }
+
+        for (x, y) in by_height.iter().rev() {
+            if let Some((t_x, t_y)) = flow[*y][*x] {
+                catchment[*y][*x] = catchment[t_y][t_x];
+            }
+        }
+
+        Drainage {
+            flow,
+            ambiguous,
+            accumulation,
+            sinks,
+            catchment,
+        }
+    }
+
+    // Basin tiles touching a tile outside their own basin
+    fn basin_outline(&self, basins: &Basins) -> Vec<Vec<bool>> {
+        let mut outline = vec![vec![false; self.width]; self.height];
+
+        for (y, row) in outline.iter_mut().enumerate() {
+            for (x, edge) in row.iter_mut().enumerate() {
+                *edge = basins.labels[y][x].is_some()
+                    && self
+                        .neighbours(x, y, Connectivity::Four)
+                        .iter()
+                        .any(|(n_x, n_y)| basins.labels[*n_y][*n_x] != basins.labels[y][x]);
+            }
+        }
+
+        outline
+    }
+
+    // Heights on a graded background, low points in bold red and basin outlines in black
+    pub fn heatmap(&self) -> String {
+        let basins = self.label_basins();
+        let outline = self.basin_outline(&basins);
+
+        self.tiles
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                let mut line = row
+                    .iter()
+                    .enumerate()
+                    .map(|(x, height)| {
+                        let [r, g, b] = height_colour(*height);
+                        let fg = if basins.low_points.contains(&(x, y)) {
+                            "1;31"
+                        } else if outline[y][x] {
+                            "30"
+                        } else {
+                            "37"
+                        };
+
+                        format!("\x1b[{};48;2;{};{};{}m{}", fg, r, g, b, height)
+                    })
+                    .collect::<String>();
+
+                line.push_str("\x1b[0m");
+                line
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // Every tile becomes a `scale` sized square; low points are filled red and the
+    // sides of a tile facing another basin are drawn black
+    pub fn dump(&self, filename: &str, scale: usize) -> Result<()> {
+        let basins = self.label_basins();
+        let scale = scale.max(1);
+        let mut f = std::fs::File::create(filename)?;
+        let mut image: Vec<u8> = vec![];
+
+        for p_y in (0..self.height * scale).rev() {
+            for p_x in 0..self.width * scale {
+                let (x, y) = (p_x / scale, p_y / scale);
+                let label = basins.labels[y][x];
+
+                let side = match (p_x % scale, p_y % scale) {
+                    (0, _) if x > 0 => Some((x - 1, y)),
+                    (0, _) => None,
+                    (_, 0) if y > 0 => Some((x, y - 1)),
+                    _ => None,
+                };
+                let on_outline = side.is_some_and(|(s_x, s_y)| basins.labels[s_y][s_x] != label);
+
+                let [r, g, b] = if on_outline {
+                    [0, 0, 0]
+                } else if basins.low_points.contains(&(x, y)) {
+                    [220, 20, 20]
+                } else {
+                    height_colour(self.tiles[y][x])
+                };
+
+                image.extend([r, g, b, 255]);
+            }
+        }
+
+        write_rgba_from_u8(
+            &mut f,
+            &image,
+            (self.width * scale) as u32,
+            (self.height * scale) as u32,
+        )?;
+
+        Ok(())
+    }
+
+    // Tile edges separating heights below `level` from the rest, chained into
+    // SVG path data with tiles as unit squares
+    pub fn contour(&self, level: u32) -> String {
+        let mut edges: Vec<((usize, usize), (usize, usize))> = vec![];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let below = self.tiles[y][x] < level;
+
+                if x + 1 < self.width && (self.tiles[y][x + 1] < level) != below {
+                    edges.push(((x + 1, y), (x + 1, y + 1)));
+                }
+
+                if y + 1 < self.height && (self.tiles[y + 1][x] < level) != below {
+                    edges.push(((x, y + 1), (x + 1, y + 1)));
+                }
+            }
+        }
+
+        let mut at: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+
+        for (i, (a, b)) in edges.iter().enumerate() {
+            at.entry(*a).or_default().push(i);
+            at.entry(*b).or_default().push(i);
+        }
+
+        // Open lines have to be walked from one of their ends, loops from anywhere
+        let starts: Vec<(usize, usize)> = edges
+            .iter()
+            .flat_map(|(a, b)| [*a, *b])
+            .filter(|p| at[p].len() % 2 == 1)
+            .chain(edges.iter().map(|(a, _)| *a))
+            .collect();
+
+        let mut used = vec![false; edges.len()];
+        let mut paths = vec![];
+
+        for start in starts {
+            while at[&start].iter().any(|i| !used[*i]) {
+                let mut points = vec![start];
+                let mut current = start;
+
+                while let Some(i) = at[&current].iter().find(|i| !used[**i]) {
+                    used[*i] = true;
+                    current = if edges[*i].0 == current {
+                        edges[*i].1
+                    } else {
+                        edges[*i].0
+                    };
+                    points.push(current);
+                }
+
+                let closed = points.len() > 2 && points.first() == points.last();
+
+                if closed {
+                    points.pop();
+                }
+
+                let mut path = points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (x, y))| format!("{}{},{}", if i == 0 { "M" } else { "L" }, x, y))
+                    .collect::<Vec<String>>()
+                    .join(" ");
+
+                if closed {
+                    path.push_str(" Z");
+                }
+
+                paths.push(path);
+            }
+        }
+
+        paths.join(" ")
+    }
+
+    pub fn contours_svg(&self) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\">\n",
+            self.width, self.height
+        );
+
+        for level in 1..=9 {
+            let path = self.contour(level);
+
+            if path.is_empty() {
+                continue;
+            }
+
+            let [r, g, b] = height_colour(level);
+
+            svg.push_str(&format!(
+                "  <path class=\"level-{}\" d=\"{}\" fill=\"none\" stroke=\"rgb({},{},{})\" stroke-width=\"0.1\"/>\n",
+                level, path, r, g, b
+            ));
+        }
+
+        svg.push_str("</svg>\n");
+        svg
     }
 
     pub fn mult_three_largest_basins(&self) -> usize {
-        let mut basins = self.find_basins();
+        let mut basins = self.label_basins().sizes;
 
         basins.sort_unstable();
 
-        basins.iter().rev().take(3).fold(1, |prod, b| b * prod)
+        basins.iter().rev().take(3).product()
     }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Window {
     pub north: Option<u32>,
+    pub north_east: Option<u32>,
     pub east: Option<u32>,
+    pub south_east: Option<u32>,
     pub south: Option<u32>,
+    pub south_west: Option<u32>,
     pub west: Option<u32>,
+    pub north_west: Option<u32>,
 
     pub center: u32,
 }
 
 impl Window {
-    pub fn is_low_point(&self) -> bool {
-        if self.center >= self.north.unwrap_or(u32::MAX) {
-            return false;
-        }
-        if self.center >= self.east.unwrap_or(u32::MAX) {
-            return false;
-        }
-        if self.center >= self.south.unwrap_or(u32::MAX) {
-            return false;
-        }
-        if self.center >= self.west.unwrap_or(u32::MAX) {
-            return false;
-        }
+    pub fn neighbours(&self) -> Vec<u32> {
+        [
+            self.north,
+            self.north_east,
+            self.east,
+            self.south_east,
+            self.south,
+            self.south_west,
+            self.west,
+            self.north_west,
+        ]
+        .iter()
+        .flatten()
+        .copied()
+        .collect()
+    }
 
-        true
+    pub fn is_low_point(&self) -> bool {
+        self.neighbours().iter().all(|n| self.center < *n)
     }
 }
 
@@ -256,4 +845,221 @@ mod tests {
 
         assert_eq!(map.mult_three_largest_basins(), 1134);
     }
+
+    #[test]
+    fn test_label_basins() {
+        let map = Map::from(
+            "2199943210\n\
+                3987894921\n\
+                9856789892\n\
+                8767896789\n\
+                9899965678",
+        );
+
+        let basins = map.label_basins();
+
+        assert_eq!(basins.sizes, vec![3, 9, 14, 9]);
+        assert_eq!(basins.low_points, vec![(1, 0), (9, 0), (2, 2), (6, 4)]);
+        assert_eq!(
+            basins.labels[0],
+            vec![
+                Some(0),
+                Some(0),
+                None,
+                None,
+                None,
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(1)
+            ]
+        );
+        assert_eq!(basins.labels[4][9], Some(3));
+    }
+
+    #[test]
+    fn test_large_basin() {
+        let row = "1".repeat(300);
+        let input = vec![row; 300].join("\n");
+        let map = Map::from(input.as_str());
+
+        assert_eq!(map.find_basins(), vec![90000]);
+    }
+
+    #[test]
+    fn test_peek_eight() {
+        let map = Map::from("123\n456\n789");
+
+        assert_eq!(map.peek(0, 0).unwrap().neighbours(), vec![2, 4]);
+        assert_eq!(
+            map.peek_with(1, 1, Connectivity::Eight)
+                .unwrap()
+                .neighbours(),
+            vec![2, 3, 6, 9, 8, 7, 4, 1]
+        );
+        assert_eq!(
+            map.peek_with(0, 2, Connectivity::Eight)
+                .unwrap()
+                .neighbours(),
+            vec![4, 5, 8]
+        );
+    }
+
+    #[test]
+    fn test_plateau_low_points() {
+        let map = Map::from(
+            "55555\n\
+                51159\n\
+                55552\n\
+                35555",
+        );
+        let options = |plateau, connectivity| BasinOptions {
+            plateau,
+            connectivity,
+            ..BasinOptions::default()
+        };
+
+        assert_eq!(
+            map.find_low_points_with(&options(Plateau::Ignore, Connectivity::Four)),
+            vec![(4, 2), (0, 3)]
+        );
+        assert_eq!(
+            map.find_low_points_with(&options(Plateau::Representative, Connectivity::Four)),
+            vec![(1, 1), (4, 2), (0, 3)]
+        );
+        assert_eq!(
+            map.find_low_points_with(&options(Plateau::Region, Connectivity::Four)),
+            vec![(1, 1), (2, 1), (4, 2), (0, 3)]
+        );
+        assert_eq!(
+            map.find_low_points_with(&options(Plateau::Region, Connectivity::Eight)),
+            vec![(1, 1), (2, 1), (4, 2), (0, 3)]
+        );
+        assert_eq!(map.find_low_points().len(), 2);
+    }
+
+    #[test]
+    fn test_basin_walls() {
+        let map = Map::from(
+            "2199943210\n\
+                3987894921\n\
+                9856789892\n\
+                8767896789\n\
+                9899965678",
+        );
+
+        let basins = map.label_basins_with(&BasinOptions {
+            wall: Wall::AtLeast(8),
+            ..BasinOptions::default()
+        });
+
+        assert_eq!(basins.sizes, vec![3, 9, 7, 6]);
+
+        let basins = map.label_basins_with(&BasinOptions {
+            wall: Wall::Predicate(|height| height == 9),
+            connectivity: Connectivity::Eight,
+            ..BasinOptions::default()
+        });
+
+        // Diagonal gaps between the 9s let every basin leak into one
+        assert_eq!(basins.sizes, vec![35]);
+    }
+
+    #[test]
+    fn test_drainage() {
+        let map = Map::from(
+            "2199943210\n\
+                3987894921\n\
+                9856789892\n\
+                8767896789\n\
+                9899965678",
+        );
+
+        let drainage = map.drainage(Connectivity::Four);
+
+        assert_eq!(drainage.sinks, vec![(1, 0), (9, 0), (2, 2), (6, 4)]);
+        assert_eq!(drainage.flow[0][0], Some((1, 0)));
+        assert_eq!(drainage.accumulation[0][1], 6);
+        assert_eq!(drainage.catchment_sizes().iter().sum::<usize>(), 50);
+        assert_eq!(drainage.catchment[1][0], 0);
+        assert_eq!(drainage.catchment[4][9], 3);
+    }
+
+    #[test]
+    fn test_ambiguous_drainage() {
+        let map = Map::from("131\n242");
+        let drainage = map.drainage(Connectivity::Four);
+
+        assert_eq!(
+            drainage.ambiguous,
+            vec![vec![false, true, false], vec![false, true, false]]
+        );
+        assert_eq!(drainage.sinks, vec![(0, 0), (2, 0)]);
+        assert_eq!(drainage.accumulation, vec![vec![2, 1, 4], vec![1, 1, 2]]);
+        assert_eq!(drainage.catchment, vec![vec![0, 1, 1], vec![0, 1, 1]]);
+        assert_eq!(drainage.to_string(), "o?o\n^?^");
+    }
+
+    #[test]
+    fn test_flat_drainage() {
+        let map = Map::from("555\n555\n551");
+        let drainage = map.drainage(Connectivity::Four);
+
+        assert_eq!(drainage.sinks, vec![(2, 2)]);
+        assert_eq!(drainage.accumulation[2][2], 9);
+        assert_eq!(drainage.catchment_sizes(), vec![9]);
+        assert_eq!(drainage.to_string(), "??v\n??v\n>>o");
+        assert!(drainage.ambiguous[0][0] && !drainage.ambiguous[2][0]);
+
+        // A whole plateau of low ground is one sink
+        let map = Map::from("222\n211\n211");
+        let drainage = map.drainage(Connectivity::Four);
+
+        assert_eq!(drainage.sinks, vec![(1, 1)]);
+        assert_eq!(drainage.accumulation[1][1], 9);
+        assert_eq!(drainage.to_string(), "?vv\n>o<\n>^?");
+    }
+
+    #[test]
+    fn test_height_colour() {
+        assert_eq!(height_colour(0), [8, 48, 107]);
+        assert_eq!(height_colour(3), [35, 139, 69]);
+        assert_eq!(height_colour(9), [245, 245, 245]);
+        assert_eq!(height_colour(12), height_colour(9));
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let map = Map::from("19\n99");
+        let heatmap = map.heatmap();
+
+        assert_eq!(heatmap.lines().count(), 2);
+        assert!(heatmap.starts_with("\x1b[1;31;48;2;"));
+        assert!(heatmap.lines().all(|line| line.ends_with("\x1b[0m")));
+    }
+
+    #[test]
+    fn test_contour() {
+        let map = Map::from("19\n99");
+
+        assert_eq!(map.contour(5), "M1,0 L1,1 L0,1");
+        assert_eq!(map.contour(0), "");
+
+        let map = Map::from("999\n919\n999");
+
+        assert_eq!(map.contour(5), "M1,1 L2,1 L2,2 L1,2 Z");
+
+        let svg = Map::from(
+            "2199943210\n\
+                3987894921\n\
+                9856789892\n\
+                8767896789\n\
+                9899965678",
+        )
+        .contours_svg();
+
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<path").count(), 9);
+    }
 }
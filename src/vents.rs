@@ -1,136 +1,241 @@
+use crate::overlap::count_covered;
+use anyhow::{Context, Result};
+use png_encode_mini::write_rgba_from_u8;
 use std::collections::HashMap;
 
-#[derive(Debug, PartialEq)]
-pub struct Map {
-    pub vents_without_diag: HashMap<(u64, u64), u64>,
-    pub vents_with_diag: HashMap<(u64, u64), u64>,
+// Largest side, in cells, of `Map::heatmap` and the image written by `Map::dump`
+pub const MAX_HEATMAP_SIDE: u64 = 2048;
+
+fn delta(from: (u64, u64), to: (u64, u64)) -> (i64, i64) {
+    (to.0 as i64 - from.0 as i64, to.1 as i64 - from.1 as i64)
 }
 
-impl From<&str> for Map {
-    fn from(input: &str) -> Self {
-        let mut vents_without_diag: HashMap<(u64, u64), u64> = HashMap::new();
-        let mut vents_with_diag: HashMap<(u64, u64), u64> = HashMap::new();
-
-        input.lines().for_each(|line| {
-            let coords: Vec<Vec<u64>> = line
-                .split(" -> ")
-                .map(|coord| {
-                    coord
-                        .split(',')
-                        .map(|v| v.parse::<u64>())
-                        .filter_map(Result::ok)
-                        .collect::<Vec<u64>>()
-                })
-                .collect();
-            let from_x = coords
-                .get(0)
-                .expect("Invalid input")
-                .get(0)
-                .expect("Invalid input");
-            let from_y = coords
-                .get(0)
-                .expect("Invalid input")
-                .get(1)
-                .expect("Invalid input");
-            let to_x = coords
-                .get(1)
-                .expect("Invalid input")
-                .get(0)
-                .expect("Invalid input");
-            let to_y = coords
-                .get(1)
-                .expect("Invalid input")
-                .get(1)
-                .expect("Invalid input");
-
-            if from_x == to_x {
-                // Vertical line
-                let range = match from_y < to_y {
-                    true => *from_y..=*to_y,
-                    false => *to_y..=*from_y,
-                };
-                for y in range {
-                    *vents_without_diag.entry((*from_x, y)).or_insert(0) += 1;
-                    *vents_with_diag.entry((*from_x, y)).or_insert(0) += 1;
-                }
-            } else if from_y == to_y {
-                // Horizontal line
-                let range = match from_x < to_x {
-                    true => *from_x..=*to_x,
-                    false => *to_x..=*from_x,
-                };
-                for x in range {
-                    *vents_without_diag.entry((x, *from_y)).or_insert(0) += 1;
-                    *vents_with_diag.entry((x, *from_y)).or_insert(0) += 1;
-                }
-            } else if to_x > from_x {
-                // ->
-                if to_y > from_y {
-                    // \
-                    //  v
-                    for step in 0..=(*to_y - *from_y) {
-                        *vents_with_diag
-                            .entry((*from_x + step, *from_y + step))
-                            .or_insert(0) += 1;
-                    }
-                } else {
-                    //  ^
-                    // /
-                    for step in 0..=(*from_y - *to_y) {
-                        *vents_with_diag
-                            .entry((*from_x + step, *from_y - step))
-                            .or_insert(0) += 1;
-                    }
-                }
-            } else {
-                // <-
-                if to_y > from_y {
-                    //  /
-                    // v
-                    for step in 0..=(*to_y - *from_y) {
-                        *vents_with_diag
-                            .entry((*from_x - step, *from_y + step))
-                            .or_insert(0) += 1;
-                    }
-                } else {
-                    // ^
-                    //  \
-                    for step in 0..=(*from_y - *to_y) {
-                        *vents_with_diag
-                            .entry((*from_x - step, *from_y - step))
-                            .or_insert(0) += 1;
-                    }
-                }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SegmentKind {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    Oblique,
+}
+
+impl SegmentKind {
+    pub const ALL: [SegmentKind; 4] = [
+        SegmentKind::Horizontal,
+        SegmentKind::Vertical,
+        SegmentKind::Diagonal,
+        SegmentKind::Oblique,
+    ];
+    pub const AXIS: [SegmentKind; 2] = [SegmentKind::Horizontal, SegmentKind::Vertical];
+
+    pub fn between(from: (u64, u64), to: (u64, u64)) -> Self {
+        let (dx, dy) = delta(from, to);
+
+        if dx == 0 {
+            SegmentKind::Vertical
+        } else if dy == 0 {
+            SegmentKind::Horizontal
+        } else if dx.abs() == dy.abs() {
+            SegmentKind::Diagonal
+        } else {
+            SegmentKind::Oblique
+        }
+    }
+
+    pub fn is_axis(&self) -> bool {
+        matches!(self, SegmentKind::Horizontal | SegmentKind::Vertical)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub from: (u64, u64),
+    pub to: (u64, u64),
+    pub kind: SegmentKind,
+}
+
+fn parse_coord(coord: &str) -> Result<(u64, u64)> {
+    let (x, y) = coord
+        .trim()
+        .split_once(',')
+        .with_context(|| format!("Missing comma in coordinate {:?}", coord))?;
+
+    Ok((
+        x.parse::<u64>()
+            .with_context(|| format!("Invalid x coordinate {:?}", x))?,
+        y.parse::<u64>()
+            .with_context(|| format!("Invalid y coordinate {:?}", y))?,
+    ))
+}
+
+impl TryFrom<&str> for Segment {
+    type Error = anyhow::Error;
+
+    fn try_from(line: &str) -> Result<Self> {
+        let (from, to) = line
+            .split_once(" -> ")
+            .with_context(|| format!("Missing arrow in segment {:?}", line))?;
+
+        Ok(Segment::new(parse_coord(from)?, parse_coord(to)?))
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+impl Segment {
+    pub fn new(from: (u64, u64), to: (u64, u64)) -> Self {
+        Segment {
+            from,
+            to,
+            kind: SegmentKind::between(from, to),
+        }
+    }
+
+    fn delta(&self) -> (i64, i64) {
+        delta(self.from, self.to)
+    }
+
+    // Every lattice point on the line, stepping by the delta divided by its gcd
+    pub fn points(&self) -> impl Iterator<Item = (u64, u64)> {
+        let (dx, dy) = self.delta();
+        let steps = gcd(dx.unsigned_abs(), dy.unsigned_abs()) as i64;
+        let (step_x, step_y) = match steps {
+            0 => (0, 0),
+            _ => (dx / steps, dy / steps),
+        };
+        let (from_x, from_y) = (self.from.0 as i64, self.from.1 as i64);
+
+        (0..=steps).map(move |i| ((from_x + i * step_x) as u64, (from_y + i * step_y) as u64))
+    }
+
+    // Squares of `bin` by `bin` coordinates, counted from `origin`, that the segment
+    // has a lattice point in. Jumps straight from one square's edge to the next, so
+    // it takes as many steps as there are squares rather than points.
+    fn bins(&self, origin: (u64, u64), bin: u64) -> Vec<(u64, u64)> {
+        let (dx, dy) = self.delta();
+        let steps = gcd(dx.unsigned_abs(), dy.unsigned_abs()) as i64;
+        let (step_x, step_y) = match steps {
+            0 => (0, 0),
+            _ => (dx / steps, dy / steps),
+        };
+        let (origin_x, origin_y, bin) = (origin.0 as i64, origin.1 as i64, bin as i64);
+
+        // Steps left until the coordinate moving by `step` leaves its square
+        let until_edge = |at: i64, origin: i64, step: i64| -> i64 {
+            let square = (at - origin) / bin;
+
+            match step.signum() {
+                1 => (origin + (square + 1) * bin - at + step - 1) / step,
+                -1 => (at - (origin + square * bin) + 1 - step - 1) / -step,
+                _ => i64::MAX,
             }
-        });
+        };
 
-        Map {
-            vents_without_diag,
-            vents_with_diag,
+        let mut bins = vec![];
+        let mut i = 0;
+
+        while i <= steps {
+            let (x, y) = (
+                self.from.0 as i64 + i * step_x,
+                self.from.1 as i64 + i * step_y,
+            );
+
+            bins.push((((x - origin_x) / bin) as u64, ((y - origin_y) / bin) as u64));
+            i += until_edge(x, origin_x, step_x).min(until_edge(y, origin_y, step_y));
         }
+
+        bins
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SegmentReport {
+    pub segment: Segment,
+    pub crosses: Vec<usize>,
+    pub danger_points: Vec<(u64, u64)>,
+}
+
+// Black for empty cells, then yellow to red as the overlap count approaches `max`
+fn heat_colour(count: u64, max: u64) -> [u8; 4] {
+    match count {
+        0 => [0, 0, 0, 255],
+        _ => [255, (255 - 255 * (count - 1) / max.max(1)) as u8, 0, 255],
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Map {
+    pub segments: Vec<Segment>,
+}
+
+impl TryFrom<&str> for Map {
+    type Error = anyhow::Error;
+
+    fn try_from(input: &str) -> Result<Self> {
+        let segments = input
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                Segment::try_from(line).with_context(|| format!("Invalid vent on line {}", i + 1))
+            })
+            .collect::<Result<Vec<Segment>>>()?;
+
+        Ok(Map { segments })
     }
 }
 
 impl Map {
+    pub fn segments_of<'a>(
+        &'a self,
+        kinds: &'a [SegmentKind],
+    ) -> impl Iterator<Item = &'a Segment> + 'a {
+        self.segments.iter().filter(|s| kinds.contains(&s.kind))
+    }
+
+    pub fn oblique_segments(&self) -> Vec<&Segment> {
+        self.segments_of(&[SegmentKind::Oblique]).collect()
+    }
+
+    pub fn count_overlaps(&self, kinds: &[SegmentKind], threshold: u64) -> usize {
+        count_covered(
+            &self.segments_of(kinds).copied().collect::<Vec<Segment>>(),
+            threshold,
+        )
+    }
+
+    pub fn coverage(&self, kinds: &[SegmentKind]) -> HashMap<(u64, u64), u64> {
+        let mut vents: HashMap<(u64, u64), u64> = HashMap::new();
+
+        for segment in self.segments_of(kinds) {
+            for point in segment.points() {
+                *vents.entry(point).or_insert(0) += 1;
+            }
+        }
+
+        vents
+    }
+
     pub fn get_count_without_diagonals(&self) -> usize {
-        self.vents_without_diag
-            .values()
-            .filter(|v| v > &&1u64)
-            .count()
+        self.count_overlaps(&SegmentKind::AXIS, 2)
     }
 
     pub fn get_count_with_diagonals(&self) -> usize {
-        self.vents_with_diag.values().filter(|v| v > &&1u64).count()
+        self.count_overlaps(&SegmentKind::ALL, 2)
     }
 
     pub fn render(&self, canvas_x: u64, canvas_y: u64) -> Vec<Vec<u64>> {
+        let vents = self.coverage(&SegmentKind::ALL);
         let mut rows = vec![];
 
         for y in 0..canvas_y {
             let mut row: Vec<u64> = vec![];
 
             for x in 0..canvas_x {
-                row.push(*self.vents_with_diag.get(&(x, y)).unwrap_or(&0));
+                row.push(*vents.get(&(x, y)).unwrap_or(&0));
             }
 
             rows.push(row)
@@ -138,6 +243,104 @@ impl Map {
 
         rows
     }
+
+    pub fn bounds(&self) -> Option<((u64, u64), (u64, u64))> {
+        let points = self.segments.iter().flat_map(|s| [s.from, s.to]);
+
+        let min_x = points.clone().map(|(x, _)| x).min()?;
+        let min_y = points.clone().map(|(_, y)| y).min()?;
+        let max_x = points.clone().map(|(x, _)| x).max()?;
+        let max_y = points.map(|(_, y)| y).max()?;
+
+        Some(((min_x, min_y), (max_x, max_y)))
+    }
+
+    pub fn heatmap(&self) -> Vec<Vec<u64>> {
+        self.heatmap_with(MAX_HEATMAP_SIDE)
+    }
+
+    // Coverage over the bounding box, shrunk so that neither side exceeds `max_side`
+    // cells. Each cell then counts the segments passing through its square of
+    // coordinates, which is plain coverage as long as no shrinking is needed.
+    pub fn heatmap_with(&self, max_side: u64) -> Vec<Vec<u64>> {
+        let ((min_x, min_y), (max_x, max_y)) = match self.bounds() {
+            Some(bounds) => bounds,
+            None => return vec![],
+        };
+
+        let (width, height) = (max_x - min_x + 1, max_y - min_y + 1);
+        let bin = width.max(height).div_ceil(max_side.max(1));
+        let mut heatmap =
+            vec![vec![0; width.div_ceil(bin) as usize]; height.div_ceil(bin) as usize];
+
+        for segment in &self.segments {
+            for (x, y) in segment.bins((min_x, min_y), bin) {
+                heatmap[y as usize][x as usize] += 1;
+            }
+        }
+
+        heatmap
+    }
+
+    pub fn dump(&self, filename: &str) -> Result<()> {
+        let heatmap = self.heatmap();
+        let height = heatmap.len();
+        let width = heatmap.first().context("Nothing to dump")?.len();
+        let max = heatmap.iter().flatten().copied().max().unwrap_or(0);
+
+        let mut image: Vec<u8> = vec![];
+
+        for row in heatmap.iter().rev() {
+            for count in row {
+                image.extend(heat_colour(*count, max));
+            }
+        }
+
+        let mut f = std::fs::File::create(filename)?;
+
+        write_rgba_from_u8(&mut f, &image, width as u32, height as u32)?;
+
+        Ok(())
+    }
+
+    pub fn segment_reports(&self) -> Vec<SegmentReport> {
+        let mut covering: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+
+        for (i, segment) in self.segments.iter().enumerate() {
+            for point in segment.points() {
+                covering.entry(point).or_default().push(i);
+            }
+        }
+
+        self.segments
+            .iter()
+            .enumerate()
+            .map(|(i, segment)| {
+                let mut crosses = vec![];
+                let mut danger_points = vec![];
+
+                for point in segment.points() {
+                    let here = &covering[&point];
+
+                    if here.len() >= 2 {
+                        danger_points.push(point);
+                        crosses.extend(here.iter().filter(|other| **other != i));
+                    }
+                }
+
+                crosses.sort_unstable();
+                crosses.dedup();
+                danger_points.sort_unstable();
+                danger_points.dedup();
+
+                SegmentReport {
+                    segment: *segment,
+                    crosses,
+                    danger_points,
+                }
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -146,7 +349,7 @@ mod tests {
 
     #[test]
     fn test_without_diag() {
-        let map = Map::from(
+        let map = Map::try_from(
             "0,9 -> 5,9\n\
                                          8,0 -> 0,8\n\
                                          9,4 -> 3,4\n\
@@ -157,14 +360,15 @@ mod tests {
                                          3,4 -> 1,4\n\
                                          0,0 -> 8,8\n\
                                          5,5 -> 8,2",
-        );
+        )
+        .unwrap();
 
         assert_eq!(map.get_count_without_diagonals(), 5);
     }
 
     #[test]
     fn test_with_diag() {
-        let map = Map::from(
+        let map = Map::try_from(
             "0,9 -> 5,9\n\
                                          8,0 -> 0,8\n\
                                          9,4 -> 3,4\n\
@@ -175,14 +379,15 @@ mod tests {
                                          3,4 -> 1,4\n\
                                          0,0 -> 8,8\n\
                                          5,5 -> 8,2",
-        );
+        )
+        .unwrap();
 
         assert_eq!(map.get_count_with_diagonals(), 12);
     }
 
     #[test]
     fn test_diag1() {
-        let map = Map::from("1,1 -> 4,4");
+        let map = Map::try_from("1,1 -> 4,4").unwrap();
         assert_eq!(
             map.render(5, 5),
             vec![
@@ -197,7 +402,7 @@ mod tests {
 
     #[test]
     fn test_diag2() {
-        let map = Map::from("4,4 -> 1,1");
+        let map = Map::try_from("4,4 -> 1,1").unwrap();
         assert_eq!(
             map.render(5, 5),
             vec![
@@ -212,7 +417,7 @@ mod tests {
 
     #[test]
     fn test_diag3() {
-        let map = Map::from("3,1 -> 0,4");
+        let map = Map::try_from("3,1 -> 0,4").unwrap();
         assert_eq!(
             map.render(5, 5),
             vec![
@@ -227,7 +432,7 @@ mod tests {
 
     #[test]
     fn test_diag4() {
-        let map = Map::from("0,4 -> 3,1");
+        let map = Map::try_from("0,4 -> 3,1").unwrap();
         assert_eq!(
             map.render(5, 5),
             vec![
@@ -239,4 +444,129 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_kinds() {
+        assert_eq!(
+            Segment::try_from("0,9 -> 5,9").unwrap().kind,
+            SegmentKind::Horizontal
+        );
+        assert_eq!(
+            Segment::try_from("2,2 -> 2,1").unwrap().kind,
+            SegmentKind::Vertical
+        );
+        assert_eq!(
+            Segment::try_from("5,5 -> 8,2").unwrap().kind,
+            SegmentKind::Diagonal
+        );
+        assert_eq!(
+            Segment::try_from("0,0 -> 4,2").unwrap().kind,
+            SegmentKind::Oblique
+        );
+    }
+
+    #[test]
+    fn test_oblique() {
+        let map = Map::try_from("0,0 -> 4,2\n0,4 -> 3,1\n3,0 -> 0,4").unwrap();
+
+        assert_eq!(
+            map.oblique_segments(),
+            vec![&map.segments[0], &map.segments[2]]
+        );
+        assert_eq!(
+            map.render(5, 5),
+            vec![
+                vec![1, 0, 0, 1, 0],
+                vec![0, 0, 1, 1, 0],
+                vec![0, 0, 1, 0, 1],
+                vec![0, 1, 0, 0, 0],
+                vec![2, 0, 0, 0, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let map = Map::try_from("2,1 -> 4,3\n4,1 -> 2,3\n2,2 -> 4,2").unwrap();
+
+        assert_eq!(map.bounds(), Some(((2, 1), (4, 3))));
+        assert_eq!(
+            map.heatmap(),
+            vec![vec![1, 0, 1], vec![1, 3, 1], vec![1, 0, 1]]
+        );
+        assert_eq!(heat_colour(0, 3), [0, 0, 0, 255]);
+        assert_eq!(heat_colour(1, 3), [255, 255, 0, 255]);
+        assert_eq!(heat_colour(3, 3), [255, 85, 0, 255]);
+    }
+
+    #[test]
+    fn test_heatmap_downscaled() {
+        let map = Map::try_from(
+            "0,0 -> 2999999,2999999\n\
+             2999999,0 -> 0,2999999\n\
+             0,1500000 -> 2999999,1500000\n\
+             7,3 -> 7,3",
+        )
+        .unwrap();
+
+        assert_eq!(
+            map.heatmap_with(3),
+            vec![vec![2, 0, 1], vec![1, 3, 1], vec![1, 0, 1]]
+        );
+        assert_eq!(map.heatmap().len(), MAX_HEATMAP_SIDE as usize);
+
+        let small = Map::try_from("2,1 -> 4,3\n4,1 -> 2,3\n2,2 -> 4,2").unwrap();
+
+        assert_eq!(small.heatmap_with(2), vec![vec![3, 2], vec![1, 1]]);
+
+        for segment in [
+            Segment::new((37, 5), (2, 40)),
+            Segment::new((37, 5), (2, 19)),
+            Segment::new((3, 30), (3, 2)),
+        ] {
+            for bin in 1..9 {
+                let mut stepped: Vec<(u64, u64)> = segment
+                    .points()
+                    .map(|(x, y)| ((x - 1) / bin, (y - 2) / bin))
+                    .collect();
+
+                stepped.dedup();
+
+                assert_eq!(segment.bins((1, 2), bin), stepped);
+            }
+        }
+    }
+
+    #[test]
+    fn test_segment_reports() {
+        let map = Map::try_from("0,0 -> 4,0\n2,0 -> 2,4\n3,0 -> 5,0\n0,4 -> 1,4").unwrap();
+        let reports = map.segment_reports();
+
+        assert_eq!(reports[0].crosses, vec![1, 2]);
+        assert_eq!(reports[0].danger_points, vec![(2, 0), (3, 0), (4, 0)]);
+        assert_eq!(reports[1].crosses, vec![0]);
+        assert_eq!(reports[1].danger_points, vec![(2, 0)]);
+        assert_eq!(reports[2].crosses, vec![0]);
+        assert_eq!(reports[3].crosses, Vec::<usize>::new());
+        assert!(reports[3].danger_points.is_empty());
+    }
+
+    #[test]
+    fn test_kind_filter() {
+        let map =
+            Map::try_from("1,0 -> 1,5\n1,2 -> 1,8\n1,4 -> 1,3\n0,3 -> 4,3\n0,2 -> 2,4").unwrap();
+
+        assert_eq!(map.count_overlaps(&[SegmentKind::Vertical], 2), 4);
+        assert_eq!(map.count_overlaps(&[SegmentKind::Vertical], 3), 2);
+        assert_eq!(map.count_overlaps(&SegmentKind::AXIS, 4), 1);
+        assert_eq!(map.count_overlaps(&SegmentKind::ALL, 5), 1);
+        assert_eq!(map.count_overlaps(&[SegmentKind::Diagonal], 2), 0);
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(Map::try_from("0,9 -> 5,9\n8,0 -> 0").is_err());
+        assert!(Map::try_from("0,9 => 5,9").is_err());
+        assert!(Segment::try_from("a,1 -> 2,2").is_err());
+    }
 }